@@ -3,10 +3,8 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use remote_settings_client::client::net::ViaductClient;
-use remote_settings_client::client::{
-    FileStorage, RingVerifier, DEFAULT_SERVER_URL, PROD_CERT_ROOT_HASH,
-};
-use remote_settings_client::{Client, Record};
+use remote_settings_client::client::{FileStorage, RingVerifier};
+use remote_settings_client::{Client, Environment, Record};
 use serde::Deserialize;
 use serde_json::json;
 pub use url::{ParseError, Url};
@@ -79,16 +77,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     println!("\n\n");
 
-    let server_url = match env_name.as_str() {
-        "dev" => "https://remote-settings-dev.allizom.org/v1",
-        "stage" => "https://remote-settings.allizom.org/v1",
-        _ => DEFAULT_SERVER_URL,
-    };
-    let root_hash = match env_name.as_str() {
-        "dev" => "45:C3:7F:3A:09:A6:D7:0E:0F:A3:21:FB:29:75:3B:A7:99:8F:12:59:B3:27:72:76:8F:23:CC:DC:24:83:67:98",
-        "stage" => "45:C3:7F:3A:09:A6:D7:0E:0F:A3:21:FB:29:75:3B:A7:99:8F:12:59:B3:27:72:76:8F:23:CC:DC:24:83:67:98",
-        _ => PROD_CERT_ROOT_HASH,
+    let environment = match env_name.as_str() {
+        "dev" => Environment::Dev,
+        "stage" => Environment::Stage,
+        _ => Environment::Prod,
     };
+    let server_url = environment.server_url();
 
     println!(
         "Fetch all Remote Settings collections from {} server.",
@@ -108,30 +102,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         let cid = format!("{}/{}", collection.bucket, collection.collection);
         print!("{}: ", cid);
 
-        // We use different signing chains depending on the bucket/collection.
-        let signer_name = match collection.bucket.as_str() {
-            "security-state" => "onecrl",
-            "security-state-preview" => "onecrl",
-            "blocklists" => match collection.collection.as_str() {
-                "certificates" => "onecrl",
-                _ => "remote-settings",
-            },
-            _ => "remote-settings",
-        };
-
         let temp_dir = std::env::temp_dir();
         let mut client = Client::builder()
-            .server_url(server_url)
+            .environment(environment.clone())
             .http_client(Box::new(ViaductClient))
             .bucket_name(&collection.bucket)
             .collection_name(&collection.collection)
-            .cert_root_hash(root_hash.to_owned())
-            .signer_name(format!("{}.content-signature.mozilla.org", signer_name))
             .storage(Box::new(FileStorage {
                 folder: temp_dir,
                 ..FileStorage::default()
             }))
-            .verifier(Box::new(RingVerifier {}))
+            .verifier(Box::new(RingVerifier::new()))
             .build()
             .unwrap();
 
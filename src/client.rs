@@ -2,43 +2,70 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+mod attachment_cache;
+mod backoff;
+mod environment;
+mod initial_data;
+#[cfg(feature = "jexl_filter")]
+pub mod jexl;
 mod kinto_http;
 pub mod net;
+mod remote_settings;
 mod signatures;
+mod signer;
 mod storage;
 
 use anyhow::{anyhow, Context};
-use log::{debug, info};
+use blocking::{unblock, Unblock};
+use futures_util::{
+    io::{AsyncWrite, AsyncWriteExt},
+    stream, StreamExt,
+};
+use log::{debug, info, warn};
 use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     convert::{TryFrom, TryInto},
+    path::Path,
+    sync::Arc,
     time::Duration,
 };
 use url::Url;
 
-#[cfg(test)]
-use mock_instant::global::Instant;
-
-#[cfg(not(test))]
-use std::time::Instant;
-
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use thiserror::Error;
 
+use attachment_cache::{attachment_key, AttachmentIndex, Bundle};
+use backoff::{check_backoff, set_backoff};
+pub use environment::{Environment, DEV_SERVER_URL, STAGE_CERT_ROOT_HASH, STAGE_SERVER_URL};
+pub use initial_data::InitialData;
 use kinto_http::{
-    delete_record, get_changeset, get_latest_change_timestamp, patch_collection, put_record,
-    KintoError, KintoObject,
+    delete_record, find_change_timestamp, get_backoff, get_changeset, patch_collection, put_record,
+    ChangesetResponse, KintoError, KintoObject,
 };
-pub use signatures::{SignatureError, Verification};
+pub use remote_settings::{RemoteSettings, RemoteSettingsBuilder, SyncReport};
+use signatures::certificate_cache::{CertificateCache, CertificateClient};
+use signatures::revocation::RevocationVerifier;
+pub use signatures::revocation::{CertificateInfo, OneCrl, RevocationChecker};
+pub use signatures::{SignatureError, Verification, VerificationMode};
+pub use signer::SignerResolver;
+use signer::{default_signer_name, signer_from_capabilities, signer_from_metadata};
+use storage::record_id;
 pub use storage::{
     dummy_storage::DummyStorage, file_storage::FileStorage, memory_storage::MemoryStorage, Storage,
     StorageError,
 };
 
+#[cfg(feature = "sqlite_storage")]
+pub use storage::sqlite_storage::SqliteStorage;
+
 #[cfg(feature = "ring_verifier")]
 pub use crate::client::signatures::ring_verifier::RingVerifier;
 
+#[cfg(feature = "rustcrypto_verifier")]
+pub use crate::client::signatures::rustcrypto_verifier::RustCryptoVerifier;
+
 #[cfg(feature = "rc_crypto_verifier")]
 pub use crate::client::signatures::rc_crypto_verifier::RcCryptoVerifier;
 
@@ -47,6 +74,9 @@ use crate::client::signatures::dummy_verifier::DummyVerifier;
 pub const DEFAULT_SERVER_URL: &str = "https://firefox.settings.services.mozilla.com/v1";
 pub const DEFAULT_BUCKET_NAME: &str = "main";
 pub const DEFAULT_SIGNER_NAME: &str = "remote-settings.content-signature.mozilla.org";
+/// Prefix of the storage key of the time (in seconds since epoch) until which the server
+/// asked clients to back off. The key is suffixed with the server URL (`backoff-until:{url}`).
+pub const BACKOFF_STORAGE_KEY: &str = "backoff-until";
 pub const PROD_CERT_ROOT_HASH: &str = "97:E8:BA:9C:F1:2F:B3:DE:53:CC:42:A4:E6:57:7E:D6:4D:F4:93:C2:47:B4:14:FE:A0:36:81:8D:38:23:56:0E";
 
 #[derive(Debug, Error)]
@@ -63,6 +93,13 @@ pub enum ClientError {
     CompatibilityError(anyhow::Error),
     #[error("attachment data was not in the expected format: {0}")]
     AttachmentMetadataError(anyhow::Error),
+    #[error("initial data could not be loaded: {0}")]
+    InitialDataError(anyhow::Error),
+    #[error("record {id} could not be deserialized: {source}")]
+    DeserializationError {
+        id: String,
+        source: serde_json::Error,
+    },
 }
 
 #[derive(Default, Debug, Deserialize, Serialize)]
@@ -79,7 +116,7 @@ pub enum Attachment {
     Some(AttachmentMetadata),
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct AttachmentMetadata {
     pub hash: String,
     pub size: usize,
@@ -159,6 +196,20 @@ impl Record {
         self.value.get(key)
     }
 
+    /// Deserialize the record fields into the specified type.
+    ///
+    /// # Errors
+    /// If the record does not match the expected type, a [`ClientError::DeserializationError`] is returned.
+    pub fn deserialize<T>(&self) -> Result<T, ClientError>
+    where
+        T: DeserializeOwned,
+    {
+        T::deserialize(&self.value).map_err(|source| ClientError::DeserializationError {
+            id: self.id().to_string(),
+            source,
+        })
+    }
+
     /// Return the attachment metadata for this record, if any.
     ///
     /// Return values:
@@ -202,6 +253,25 @@ impl Record {
     }
 }
 
+/// What to do with records that cannot be deserialized in [`Client::get_typed`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InvalidRecordPolicy {
+    /// Log and ignore invalid records.
+    Skip,
+    /// Return an error on the first invalid record.
+    FailFast,
+    /// Ignore invalid records, and list their errors in [`TypedRecords::invalid`].
+    Collect,
+}
+
+/// Records deserialized by [`Client::get_typed`].
+#[derive(Debug)]
+pub struct TypedRecords<T> {
+    pub records: Vec<T>,
+    /// Errors of the records that could not be deserialized (see [`InvalidRecordPolicy::Collect`]).
+    pub invalid: Vec<ClientError>,
+}
+
 impl<I> std::ops::Index<I> for Record
 where
     I: serde_json::value::Index,
@@ -214,7 +284,7 @@ where
 }
 
 /// Representation of a collection on the server
-#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+#[derive(Debug, Default, PartialEq, Deserialize, Serialize, Clone)]
 pub struct Collection {
     pub bid: String,
     pub cid: String,
@@ -224,6 +294,72 @@ pub struct Collection {
     pub signer: String,
 }
 
+/// Content of a collection dump, as exported from the server.
+#[derive(Deserialize)]
+struct CollectionDump {
+    bid: Option<String>,
+    cid: Option<String>,
+    #[serde(default)]
+    metadata: KintoObject,
+    #[serde(alias = "data")]
+    records: Vec<Value>,
+    timestamp: u64,
+}
+
+/// A record that was changed during a synchronization.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RecordUpdate {
+    pub old: Record,
+    pub new: Record,
+}
+
+/// Result of [`Client::sync_with_changes`].
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct SyncResult {
+    /// The local collection after synchronization.
+    pub collection: Collection,
+    pub created: Vec<Record>,
+    pub updated: Vec<RecordUpdate>,
+    pub deleted: Vec<Record>,
+}
+
+impl SyncResult {
+    fn unchanged(collection: Collection) -> Self {
+        SyncResult {
+            collection,
+            ..SyncResult::default()
+        }
+    }
+}
+
+/// Status of an attachment in [`AttachmentsReport`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AttachmentStatus {
+    /// The attachment was downloaded and stored.
+    Downloaded,
+    /// The attachment was already stored locally.
+    AlreadyStored,
+    /// The attachment was downloaded, but then removed from the local storage to stay
+    /// within the maximum size (see [`ClientBuilder::max_attachments_size`]).
+    Evicted,
+}
+
+/// Result of [`Client::download_all_attachments`].
+#[derive(Debug, Default)]
+pub struct AttachmentsReport {
+    /// Status of the attachment of each record, keyed by record id.
+    ///
+    /// Records without attachment are not listed.
+    pub records: HashMap<String, Result<AttachmentStatus, ClientError>>,
+}
+
+impl AttachmentsReport {
+    /// Return true if every attachment is stored locally.
+    pub fn is_success(&self) -> bool {
+        self.records.values().all(|r| r.is_ok())
+    }
+}
+
 /// Client to fetch Remote Settings data.
 ///
 /// # Examples
@@ -252,7 +388,19 @@ pub struct Collection {
 ///
 /// ## Signature verification
 ///
-/// When no verifier is explicitly specified, a dummy verifier is used.
+/// When no verifier is explicitly specified, a dummy verifier is used, and a warning
+/// is logged on every synchronization. In order to refuse building a client that does
+/// not check signatures, use [`VerificationMode::Strict`].
+///
+/// ```rust
+/// # use remote_settings_client::{Client, VerificationMode};
+/// let result = Client::builder()
+///   .collection_name("cid")
+///   .verification_mode(VerificationMode::Strict)
+///   .build();
+///
+/// assert!(result.is_err());
+/// ```
 ///
 /// ### `ring`
 ///
@@ -264,7 +412,24 @@ pub struct Collection {
 ///
 /// let client = Client::builder()
 ///   .collection_name("cid")
-///   .verifier(Box::new(RingVerifier {}))
+///   .verifier(Box::new(RingVerifier::new()))
+///   .build()
+///   .unwrap();
+/// # }
+/// ```
+///
+/// ### RustCrypto
+///
+/// With the `rustcrypto_verifier` feature, a signature verifier leveraging the pure-Rust [RustCrypto crates](https://github.com/RustCrypto),
+/// for targets where neither `ring` nor NSS are available.
+/// ```rust
+/// # #[cfg(feature = "rustcrypto_verifier")] {
+/// # use remote_settings_client::Client;
+/// use remote_settings_client::RustCryptoVerifier;
+///
+/// let client = Client::builder()
+///   .collection_name("cid")
+///   .verifier(Box::new(RustCryptoVerifier::new()))
 ///   .build()
 ///   .unwrap();
 /// # }
@@ -329,6 +494,29 @@ pub struct Collection {
 /// verifier will be used to confirm that hash, and if it does not match a
 /// verification error will be returned.
 ///
+/// Downloaded attachments are kept in the local storage. They are removed when their
+/// records change, when the total size exceeds [`ClientBuilder::max_attachments_size`],
+/// or explicitly with [`Client::purge_attachments`].
+///
+/// ## Initial data
+///
+/// A dump of the collection can be shipped with the application, in order to have
+/// data before the first synchronization. It is verified like data from the server,
+/// and the next synchronization only fetches the changes since the dump.
+///
+/// ```rust
+/// # use remote_settings_client::Client;
+/// # fn main() {
+/// # let dump_path = std::path::PathBuf::from("cid.json");
+/// let client = Client::builder()
+///   .collection_name("cid")
+///   // Or `.initial_data(include_bytes!("../dumps/main/cid.json"))`
+///   .initial_data(dump_path)
+///   .build()
+///   .unwrap();
+/// # }
+/// ```
+///
 /// ## Write Operations
 ///
 /// ```no_run
@@ -365,35 +553,156 @@ pub struct Collection {
 /// ```
 ///
 #[derive(Builder, Debug)]
-#[builder(pattern = "owned")] // No clone because of Box<dyn...>
+#[builder(pattern = "owned", build_fn(private, name = "build_client"))] // No clone because of Box<dyn...>
 pub struct Client {
+    /// Server and root certificate to use.
+    ///
+    /// It cannot be combined with [`ClientBuilder::server_url`] or
+    /// [`ClientBuilder::cert_root_hash`]: use [`Environment::Custom`] for another server.
+    #[builder(default)]
+    environment: Environment,
     #[builder(setter(into), default = "DEFAULT_SERVER_URL.to_owned()")]
     server_url: String,
     #[builder(setter(into), default = "DEFAULT_BUCKET_NAME.to_owned()")]
     bucket_name: String,
     #[builder(setter(into))]
     collection_name: String,
-    #[builder(setter(into), default = "DEFAULT_SIGNER_NAME.to_owned()")]
-    signer_name: String,
+    /// Expected signer name of the collection.
+    ///
+    /// By default, it is obtained from the [`SignerResolver`] if any, from the `signer_id`
+    /// of the `signer` server capability, or else from the bucket and collection names
+    /// (eg. `onecrl.content-signature.mozilla.org` for `security-state`, and
+    /// [`DEFAULT_SIGNER_NAME`] otherwise, see [`Environment`]).
+    ///
+    /// The `signer_id` of the collection signature is not signed: if it designates another
+    /// signer, the verification fails.
+    #[builder(setter(into, strip_option), default = "None")]
+    signer_name: Option<String>,
+    /// Hook to choose the expected signer name of the collection, when not specified with
+    /// [`ClientBuilder::signer_name`].
+    #[builder(setter(strip_option), default = "None")]
+    signer_resolver: Option<Arc<dyn SignerResolver>>,
     // Box<dyn Trait> is necessary since implementation of [`Verification`] can be of any size unknown at compile time
     #[builder(default = "Box::new(DummyVerifier {})")]
     verifier: Box<dyn Verification>,
+    /// Whether building the client fails when signatures are not checked by the verifier.
+    #[builder(default)]
+    verification_mode: VerificationMode,
+    /// Check that the certificates of the chain were not revoked, for example with [`OneCrl`].
+    #[builder(setter(strip_option), default = "None")]
+    revocation_checker: Option<Arc<dyn RevocationChecker>>,
     #[builder(default = "Box::new(DummyStorage {})")]
     storage: Box<dyn Storage>,
     #[builder(default = "true")]
     sync_if_empty: bool,
     #[builder(default = "true")]
     trust_local: bool,
+    /// Collection dump to use when the local storage is empty, instead of synchronizing.
+    #[builder(setter(into, strip_option), default = "None")]
+    initial_data: Option<InitialData>,
     #[builder(private, default = "None")]
-    backoff_until: Option<Instant>,
+    backoff_until: Option<u64>,
     #[builder(default = "PROD_CERT_ROOT_HASH.to_owned()")]
     cert_root_hash: String,
     #[builder(default = "Box::new(net::DummyClient)")]
     http_client: Box<dyn net::Requester + 'static>,
+    /// Retry downloads (changesets, certificates, attachments) on transient failures.
+    #[builder(default)]
+    retry_policy: net::RetryPolicy,
+    /// Keep responses in memory, and revalidate them with conditional requests
+    /// (`ETag` and `Last-Modified`), like the changesets of the `monitor/changes`
+    /// endpoint and of the collection.
+    ///
+    /// Disabled by default: up to 64 responses can be kept in memory. Responses larger
+    /// than 1MB, including large attachments, and streamed downloads (see
+    /// [`Client::download_attachment`]) are not kept.
+    #[builder(default = "false")]
+    http_cache: bool,
+    /// Difference tolerated between the server time and the validity period of certificates.
+    ///
+    /// The server time is estimated from the `Date` header of the changesets of collections,
+    /// unless it is more than a day away from the local time.
+    #[builder(default)]
+    clock_skew_tolerance: Duration,
+    #[builder(setter(skip))]
+    certificates: Arc<CertificateCache>,
+    /// Hashes of the attachments read from the local storage, not marked as used in
+    /// the attachments index yet.
+    #[builder(setter(skip))]
+    used_attachments: Vec<String>,
     #[builder(default = "None")]
     server_info: Option<Value>,
     #[builder(setter(into, strip_option), default = "None")]
     authorization: Option<String>,
+    /// Maximum total size (in bytes) of the attachments stored locally.
+    ///
+    /// When exceeded, the least recently used attachments are removed.
+    #[builder(setter(strip_option), default = "None")]
+    max_attachments_size: Option<usize>,
+}
+
+impl ClientBuilder {
+    /// Builds a new `Client`.
+    ///
+    /// # Errors
+    /// If a required field has not been initialized, if the environment is combined with
+    /// a server URL or a root hash, or if signatures are not checked in
+    /// [`VerificationMode::Strict`], a [`ClientBuilderError`] is returned.
+    pub fn build(mut self) -> Result<Client, ClientBuilderError> {
+        if let Some(environment) = self.environment.clone() {
+            // The server and its root certificate always go together.
+            if self.server_url.is_some() || self.cert_root_hash.is_some() {
+                return Err(ClientBuilderError::ValidationError(
+                    "the environment cannot be combined with a server URL or a root hash"
+                        .to_string(),
+                ));
+            }
+            self.server_url = Some(environment.server_url().to_owned());
+            self.cert_root_hash = Some(environment.cert_root_hash().to_owned());
+        }
+        let mut client = self.build_client()?;
+        if client.verification_mode == VerificationMode::Strict
+            && !client.verifier.checks_signatures()
+        {
+            return Err(ClientBuilderError::ValidationError(
+                "a verifier that checks signatures is required in strict verification mode"
+                    .to_string(),
+            ));
+        }
+        // Revocations are irrelevant if signatures are not checked.
+        if let (Some(checker), true) = (
+            client.revocation_checker.clone(),
+            client.verifier.checks_signatures(),
+        ) {
+            let verifier = std::mem::replace(&mut client.verifier, Box::new(DummyVerifier {}));
+            client.verifier = Box::new(RevocationVerifier::new(verifier, checker));
+        }
+        if client.retry_policy.max_attempts > 1 {
+            let http_client =
+                std::mem::replace(&mut client.http_client, Box::new(net::DummyClient));
+            client.http_client = Box::new(net::RetryClient::new(
+                http_client,
+                client.retry_policy.clone(),
+            ));
+        }
+        if client.http_cache {
+            let http_client =
+                std::mem::replace(&mut client.http_client, Box::new(net::DummyClient));
+            client.http_client = Box::new(net::CachingClient::new(http_client));
+        }
+        let http_client = std::mem::replace(&mut client.http_client, Box::new(net::DummyClient));
+        client.http_client = Box::new(net::ClockClient::new(
+            http_client,
+            client.clock_skew_tolerance.as_secs(),
+        ));
+        // Certificate chains are cached according to the clock of the server.
+        let http_client = std::mem::replace(&mut client.http_client, Box::new(net::DummyClient));
+        client.http_client = Box::new(CertificateClient::new(
+            http_client,
+            client.certificates.clone(),
+        ));
+        Ok(client)
+    }
 }
 
 impl Default for Client {
@@ -424,6 +733,18 @@ impl Client {
         format!("{}/{}:collection", self.bucket_name, self.collection_name)
     }
 
+    fn _records_key(&self) -> String {
+        format!("{}/{}", self.bucket_name, self.collection_name)
+    }
+
+    fn _metadata_key(&self) -> String {
+        format!("{}/{}:metadata", self.bucket_name, self.collection_name)
+    }
+
+    fn _timestamp_key(&self) -> String {
+        format!("{}/{}:timestamp", self.bucket_name, self.collection_name)
+    }
+
     /// Return the records stored locally.
     ///
     /// # Examples
@@ -444,6 +765,8 @@ impl Client {
     ///
     /// # Behaviour
     /// * Return local data by default;
+    /// * If local data is empty, import the initial data if any. If it cannot be imported
+    ///   (eg. invalid signature), a warning is logged when falling back to synchronization;
     /// * If local data is empty and if `sync_if_empty` is `true` (*default*),
     ///   then synchronize the local data with the server and return records, otherwise
     ///   return an error.
@@ -456,83 +779,244 @@ impl Client {
     /// # Errors
     /// If an error occurs while fetching or verifying records, a [`ClientError`] is returned.
     pub async fn get(&mut self) -> Result<Vec<Record>, ClientError> {
+        Ok(self.get_collection().await?.records)
+    }
+
+    /// Return the collection stored locally, like [`Client::get`].
+    async fn get_collection(&mut self) -> Result<Collection, ClientError> {
         let storage_key = self._storage_key();
 
         debug!("Retrieve from storage with key={:?}", storage_key);
-        let read_result = self.storage.retrieve(&storage_key);
-
-        match read_result {
-            Ok(stored_bytes) => {
-                // Deserialize content of storage and surface error if fails.
-                let stored: Collection = serde_json::from_slice(&stored_bytes).map_err(|err| {
-                    StorageError::ReadError(format!("cannot deserialize collection: {}", err))
-                })?;
+        match self.load_collection() {
+            Ok(Some(mut stored)) => {
                 // Verify signature of stored data (*optional*)
                 if !self.trust_local {
                     debug!("Verify signature of local data.");
-                    self.verifier
-                        .verify(self.http_client.as_ref(), &stored, &self.cert_root_hash)
-                        .await?;
+                    self.verify_collection(&mut stored).await?;
                 }
 
-                Ok(stored.records)
+                Ok(stored)
             }
-            // If storage is empty, go on with sync() (*optional*)
-            Err(StorageError::KeyNotFound { .. }) if self.sync_if_empty => {
-                debug!("Synchronize data, without knowning which timestamp to expect.");
-                let collection = self.sync(None).await?;
-                Ok(collection.records)
+            Ok(None) => {
+                // If storage is empty, import the initial data (*optional*)
+                let mut import_error = None;
+                if self.initial_data.is_some() {
+                    match self.import_initial_data().await {
+                        Ok(collection) => return Ok(collection),
+                        Err(err) if self.sync_if_empty => warn!("Ignore initial data: {}", err),
+                        Err(err) => import_error = Some(err),
+                    }
+                }
+                // If storage is empty, go on with sync() (*optional*)
+                if self.sync_if_empty {
+                    debug!("Synchronize data, without knowning which timestamp to expect.");
+                    // The initial data was already tried.
+                    return Ok(self.sync_changes(None, false).await?.collection);
+                }
+                Err(import_error
+                    .unwrap_or_else(|| StorageError::KeyNotFound { key: storage_key }.into()))
             }
             // Otherwise, surface the error.
             Err(err) => Err(err.into()),
         }
     }
 
+    /// Return the records stored locally, deserialized into the specified type.
+    ///
+    /// Records are obtained like with [`Client::get`], and those that cannot be
+    /// deserialized are handled according to the specified `policy`.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use remote_settings_client::{Client, client::InvalidRecordPolicy};
+    /// # use serde::Deserialize;
+    /// #[derive(Deserialize)]
+    /// struct Entry {
+    ///     id: String,
+    ///     url: String,
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = Client::builder().collection_name("cid").build().unwrap();
+    /// let entries = client.get_typed::<Entry>(InvalidRecordPolicy::Skip).await?;
+    /// for entry in entries.records {
+    ///     println!("{}: {}", entry.id, entry.url);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// If an error occurs while fetching or verifying records, a [`ClientError`] is returned.
+    ///
+    /// With [`InvalidRecordPolicy::FailFast`], a [`ClientError::DeserializationError`] is returned
+    /// for the first record that cannot be deserialized.
+    pub async fn get_typed<T>(
+        &mut self,
+        policy: InvalidRecordPolicy,
+    ) -> Result<TypedRecords<T>, ClientError>
+    where
+        T: DeserializeOwned,
+    {
+        let mut typed = TypedRecords {
+            records: Vec::new(),
+            invalid: Vec::new(),
+        };
+        for record in self.get().await? {
+            match record.deserialize() {
+                Ok(v) => typed.records.push(v),
+                Err(err) => match policy {
+                    InvalidRecordPolicy::FailFast => return Err(err),
+                    InvalidRecordPolicy::Skip => warn!("Skip invalid record: {}", err),
+                    InvalidRecordPolicy::Collect => typed.invalid.push(err),
+                },
+            }
+        }
+        Ok(typed)
+    }
+
+    /// Return the records stored locally whose `filter_expression` matches the specified context.
+    ///
+    /// Records are obtained like with [`Client::get`]. Records without filter expression
+    /// always match, and records whose expression cannot be evaluated never match.
+    /// See the [`jexl`] module for the supported syntax.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use remote_settings_client::Client;
+    /// # use serde_json::json;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = Client::builder().collection_name("cid").build().unwrap();
+    /// let context = json!({
+    ///     "env": {"locale": "fr-FR", "version": "115.0.2", "appinfo": {"OS": "Linux"}}
+    /// });
+    /// let records = client.get_filtered(&context).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// If an error occurs while fetching or verifying records, a [`ClientError`] is returned.
+    #[cfg(feature = "jexl_filter")]
+    pub async fn get_filtered(&mut self, context: &Value) -> Result<Vec<Record>, ClientError> {
+        let mut records = self.get().await?;
+        records.retain(|record| match record.get("filter_expression") {
+            None | Some(Value::Null) => true,
+            Some(Value::String(expression)) if expression.trim().is_empty() => true,
+            Some(Value::String(expression)) => match jexl::evaluate(expression, context) {
+                Ok(value) => jexl::is_truthy(&value),
+                Err(err) => {
+                    warn!("Ignore record {}: {}", record.id(), err);
+                    false
+                }
+            },
+            Some(other) => {
+                warn!(
+                    "Ignore record {}: invalid filter expression {}",
+                    record.id(),
+                    other
+                );
+                false
+            }
+        });
+        Ok(records)
+    }
+
     /// Synchronize the local storage with the content of the server for this collection.
     ///
     /// # Behaviour
     /// * If stored data is up-to-date and signature of local data valid, then return local content;
     /// * Otherwise fetch content from server, merge with local content, verify signature, and return records;
     ///
+    /// See [`Client::sync_with_changes`] to obtain the list of changed records.
+    ///
     /// # Errors
     /// If an error occurs while fetching or verifying records, a [`ClientError`] is returned.
     pub async fn sync<T>(&mut self, expected: T) -> Result<Collection, ClientError>
     where
         T: Into<Option<u64>>,
     {
+        Ok(self.sync_with_changes(expected).await?.collection)
+    }
+
+    /// Synchronize the local storage with the content of the server for this collection,
+    /// and return the records that were created, updated or deleted.
+    ///
+    /// Behaves like [`Client::sync`]. If the local data was already up-to-date, the
+    /// lists of changes are empty.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use remote_settings_client::Client;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = Client::builder().collection_name("cid").build().unwrap();
+    /// let result = client.sync_with_changes(None).await?;
+    /// for update in &result.updated {
+    ///     println!("{} was updated at {}", update.new.id(), update.new.last_modified());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// If an error occurs while fetching or verifying records, a [`ClientError`] is returned.
+    pub async fn sync_with_changes<T>(&mut self, expected: T) -> Result<SyncResult, ClientError>
+    where
+        T: Into<Option<u64>>,
+    {
+        self.sync_changes(expected.into(), true).await
+    }
+
+    /// Synchronize like [`Client::sync_with_changes`], importing the initial data first
+    /// if `import_initial_data` is true and the local storage is empty.
+    async fn sync_changes(
+        &mut self,
+        expected: Option<u64>,
+        import_initial_data: bool,
+    ) -> Result<SyncResult, ClientError> {
         self.check_sync_state()?;
 
-        let storage_key = self._storage_key();
+        if !self.verifier.checks_signatures() {
+            warn!("Signatures are not verified, data from the server is trusted blindly.");
+        }
 
-        debug!("Retrieve from storage with key={:?}", storage_key);
-        let stored_bytes: Vec<u8> = self.storage.retrieve(&storage_key).unwrap_or_default();
-        let stored: Option<Collection> = serde_json::from_slice(&stored_bytes).unwrap_or(None);
+        debug!("Retrieve from storage with key={:?}", self._records_key());
+        let mut stored: Option<Collection> = self.load_collection().unwrap_or(None);
+        if stored.is_none() && import_initial_data && self.initial_data.is_some() {
+            match self.import_initial_data().await {
+                Ok(collection) => stored = Some(collection),
+                Err(err) => warn!("Ignore initial data: {}", err),
+            }
+        }
 
-        let remote_timestamp = match expected.into() {
+        let remote_timestamp = match expected {
             Some(v) => v,
             None => {
                 debug!("Obtain current timestamp.");
-                get_latest_change_timestamp(
+                // When we fetch the monitor/changes endpoint manually (ie. not from a push notification)
+                // we cannot know the current timestamp, and use 0 arbitrarily.
+                let changes = get_changeset(
                     self.http_client.as_ref(),
                     &self.server_url,
-                    &self.bucket_name,
-                    &self.collection_name,
+                    "monitor",
+                    "changes",
+                    0,
+                    None,
                 )
-                .await?
+                .await;
+                let changes = self.handle_backoff(changes)?;
+                find_change_timestamp(&changes, &self.bucket_name, &self.collection_name)?
             }
         };
 
-        if let Some(ref collection) = stored {
+        if let Some(ref mut collection) = stored {
             let up_to_date = collection.timestamp == remote_timestamp;
-            if up_to_date
-                && self
-                    .verifier
-                    .verify(self.http_client.as_ref(), collection, &self.cert_root_hash)
-                    .await
-                    .is_ok()
-            {
+            if up_to_date && self.verify_collection(collection).await.is_ok() {
                 debug!("Local data is up-to-date and valid.");
-                return Ok(stored.unwrap());
+                return Ok(SyncResult::unchanged(stored.unwrap()));
             }
         }
 
@@ -550,55 +1034,288 @@ impl Client {
             remote_timestamp,
             local_timestamp,
         )
-        .await?;
-
-        // Keep in state that the server indicated the client
-        // to backoff for a while.
-        if let Some(backoff_secs) = changeset.backoff {
-            self.backoff_until = Some(Instant::now() + Duration::from_secs(backoff_secs));
-        }
+        .await;
+        let changeset = self.handle_backoff(changeset)?;
 
         debug!(
             "Apply {} changes to {} local records",
             changeset.changes.len(),
             local_records.len()
         );
-        let merged = merge_changes(local_records, changeset.changes);
+        let (merged, changes) = merge_changes(local_records, changeset.changes);
 
-        let collection = Collection {
+        let mut collection = Collection {
             bid: self.bucket_name.clone(),
             cid: self.collection_name.clone(),
+            signer: self.signer_for(&changeset.metadata),
             metadata: changeset.metadata,
             records: merged,
             timestamp: changeset.timestamp,
-            signer: self.signer_name.clone(),
         };
 
         debug!("Verify signature after merge of changes with previous local data.");
-        self.verifier
-            .verify(self.http_client.as_ref(), &collection, &self.cert_root_hash)
-            .await?;
+        self.verify_collection(&mut collection).await?;
+
+        let mut deleted: Vec<String> = changes
+            .deleted
+            .iter()
+            .map(|record| record.id().to_string())
+            .collect();
+        if local_timestamp.is_none() {
+            // Remove records left over by an interrupted synchronization.
+            deleted.extend(
+                self.storage
+                    .retrieve_records(&self._records_key())?
+                    .iter()
+                    .filter_map(|record| record_id(record).ok().map(str::to_string)),
+            );
+        }
+        let upserted = changes
+            .created
+            .iter()
+            .chain(changes.updated.iter().map(|update| &update.new))
+            .map(|record| record.value.clone())
+            .collect();
+
+        // The index of attachments is created from the previous records, if needed.
+        self.attachment_index()?;
+        debug!("Store changes with key={:?}", self._records_key());
+        self.store_changes(&collection, upserted, &deleted)?;
+        self.prune_attachments(&collection.records)?;
+
+        Ok(SyncResult {
+            collection,
+            ..changes
+        })
+    }
+
+    /// Return the signer name specified explicitly, or by the hook.
+    fn specified_signer(&self, metadata: &Value) -> Option<String> {
+        let (bid, cid) = (&self.bucket_name, &self.collection_name);
+        self.signer_name.clone().or_else(|| {
+            self.signer_resolver
+                .as_ref()
+                .and_then(|resolver| resolver.signer_name(bid, cid, metadata))
+        })
+    }
+
+    /// Return the expected signer name of a collection, using the server capabilities
+    /// only if they were already obtained.
+    fn signer_for(&self, metadata: &Value) -> String {
+        let (bid, cid) = (&self.bucket_name, &self.collection_name);
+        self.specified_signer(metadata)
+            .or_else(|| {
+                self.server_info
+                    .as_ref()
+                    .and_then(|server_info| signer_from_capabilities(server_info, bid, cid))
+            })
+            .unwrap_or_else(|| default_signer_name(&self.environment, bid, cid))
+    }
 
-        debug!("Store collection with key={:?}", storage_key);
-        let collection_bytes: Vec<u8> = serde_json::to_string(&collection)
+    /// Verify the signature of a collection, and set its expected signer name.
+    ///
+    /// The certificate chains are kept in storage, and only downloaded again when expired.
+    async fn verify_collection(
+        &mut self,
+        collection: &mut Collection,
+    ) -> Result<(), SignatureError> {
+        if self.verifier.checks_signatures()
+            && self.server_info.is_none()
+            && self.specified_signer(&collection.metadata).is_none()
+        {
+            // The signer may be listed in the server capabilities.
+            if let Err(err) = self.server_info().await {
+                debug!("Server capabilities are not available: {}", err);
+            }
+        }
+        collection.signer = self.signer_for(&collection.metadata);
+        // The signer of the metadata is not signed, so it cannot choose the expected signer.
+        if let Some(signer) = signer_from_metadata(&collection.metadata) {
+            if self.verifier.checks_signatures() && signer != collection.signer {
+                return Err(SignatureError::InvalidCertificateSubject(format!(
+                    "{} (expected {})",
+                    signer, collection.signer
+                )));
+            }
+        }
+
+        self.certificates.load(self.storage.as_ref());
+        if let Some(x5u) = collection.metadata["signature"]["x5u"].as_str() {
+            self.certificates.expect(x5u);
+        }
+        let result = self
+            .verifier
+            .verify(self.http_client.as_ref(), collection, &self.cert_root_hash)
+            .await;
+        let clock = self.http_client.server_clock();
+        if let Err(err) = self.certificates.save(self.storage.as_mut(), &clock) {
+            warn!("Could not store certificates: {}", err);
+        }
+        result
+    }
+
+    /// Read the collection from storage, or `None` if it was never synchronized.
+    fn load_collection(&mut self) -> Result<Option<Collection>, StorageError> {
+        let timestamp = match self.storage.retrieve(&self._timestamp_key()) {
+            Ok(bytes) => String::from_utf8(bytes)
+                .ok()
+                .and_then(|s| s.parse::<u64>().ok())
+                .ok_or_else(|| {
+                    StorageError::ReadError("cannot parse collection timestamp".to_string())
+                })?,
+            Err(StorageError::KeyNotFound { .. }) => return self.migrate_collection(),
+            Err(err) => return Err(err),
+        };
+        let metadata = serde_json::from_slice(&self.storage.retrieve(&self._metadata_key())?)
             .map_err(|err| {
-                StorageError::WriteError(format!("cannot serialize collection: {}", err))
-            })?
-            .into();
-        self.storage.store(&storage_key, collection_bytes)?;
+                StorageError::ReadError(format!("cannot deserialize metadata: {}", err))
+            })?;
+        let records = self
+            .storage
+            .retrieve_records(&self._records_key())?
+            .into_iter()
+            .map(Record::new)
+            .collect();
+
+        Ok(Some(Collection {
+            bid: self.bucket_name.clone(),
+            cid: self.collection_name.clone(),
+            signer: self.signer_for(&metadata),
+            metadata,
+            records,
+            timestamp,
+        }))
+    }
+
+    /// Verify the initial data and store it.
+    async fn import_initial_data(&mut self) -> Result<Collection, ClientError> {
+        let initial_data = self.initial_data.as_ref().unwrap();
+        let dump: CollectionDump = {
+            let bytes = initial_data
+                .read()
+                .with_context(|| format!("reading {:?}", initial_data))
+                .map_err(ClientError::InitialDataError)?;
+            serde_json::from_slice(&bytes)
+                .context("parsing collection dump")
+                .map_err(ClientError::InitialDataError)?
+        };
+        let (bid, cid) = (&self.bucket_name, &self.collection_name);
+        if dump.bid.as_deref().unwrap_or(bid) != bid || dump.cid.as_deref().unwrap_or(cid) != cid {
+            return Err(ClientError::InitialDataError(anyhow!(
+                "dump of {}/{} cannot be used for {}/{}",
+                dump.bid.unwrap_or_default(),
+                dump.cid.unwrap_or_default(),
+                bid,
+                cid
+            )));
+        }
+        let mut collection = Collection {
+            bid: bid.clone(),
+            cid: cid.clone(),
+            signer: self.signer_for(&dump.metadata),
+            metadata: dump.metadata,
+            records: dump.records.into_iter().map(Record::new).collect(),
+            timestamp: dump.timestamp,
+        };
+
+        debug!("Verify signature of initial data.");
+        self.verify_collection(&mut collection).await?;
+
+        info!(
+            "Import {} records from initial data (timestamp={}).",
+            collection.records.len(),
+            collection.timestamp
+        );
+        // Remove records left over by an interrupted synchronization.
+        let leftovers: Vec<String> = self
+            .storage
+            .retrieve_records(&self._records_key())?
+            .iter()
+            .filter_map(|record| record_id(record).ok().map(str::to_string))
+            .collect();
+        let records = collection.records.iter().map(|r| r.value.clone()).collect();
+        self.store_changes(&collection, records, &leftovers)?;
 
         Ok(collection)
     }
 
+    /// Move a collection stored as a single value by previous versions into records storage.
+    fn migrate_collection(&mut self) -> Result<Option<Collection>, StorageError> {
+        let storage_key = self._storage_key();
+        let stored_bytes = match self.storage.retrieve(&storage_key) {
+            Ok(bytes) if !bytes.is_empty() => bytes,
+            Ok(_) | Err(StorageError::KeyNotFound { .. }) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let collection: Collection = serde_json::from_slice(&stored_bytes).map_err(|err| {
+            StorageError::ReadError(format!("cannot deserialize collection: {}", err))
+        })?;
+
+        debug!("Migrate collection with key={:?}", storage_key);
+        let records = collection.records.iter().map(|r| r.value.clone()).collect();
+        self.store_changes(&collection, records, &[])?;
+        self.storage.delete(&storage_key)?;
+
+        Ok(Some(collection))
+    }
+
+    /// Apply changes of records to storage, along with the new collection metadata and timestamp.
+    fn store_changes(
+        &mut self,
+        collection: &Collection,
+        upserted: Vec<Value>,
+        deleted: &[String],
+    ) -> Result<(), StorageError> {
+        if !deleted.is_empty() || !upserted.is_empty() {
+            let ids: Vec<&str> = deleted.iter().map(String::as_str).collect();
+            self.storage
+                .update_records(&self._records_key(), upserted, &ids)?;
+        }
+        let metadata_bytes = serde_json::to_vec(&collection.metadata).map_err(|err| {
+            StorageError::WriteError(format!("cannot serialize metadata: {}", err))
+        })?;
+        self.storage.store(&self._metadata_key(), metadata_bytes)?;
+        // The timestamp is stored last: if interrupted before, the same changes
+        // will be fetched and applied again on the next synchronization.
+        self.storage.store(
+            &self._timestamp_key(),
+            collection.timestamp.to_string().into_bytes(),
+        )
+    }
+
     fn check_sync_state(&mut self) -> Result<(), ClientError> {
-        if let Some(until) = self.backoff_until {
-            if Instant::now() < until {
-                let remaining_secs = (until - Instant::now()).as_secs();
-                return Err(ClientError::BackoffError(remaining_secs));
+        check_backoff(
+            &mut self.backoff_until,
+            self.storage.as_mut(),
+            &self.server_url,
+        )
+    }
+
+    /// Keep in state that the server indicated the client to backoff for a while.
+    fn set_backoff(&mut self, backoff_secs: Option<u64>) -> Result<(), ClientError> {
+        set_backoff(
+            &mut self.backoff_until,
+            self.storage.as_mut(),
+            &self.server_url,
+            backoff_secs,
+        )
+    }
+
+    /// Handle the `Backoff` header of a changeset response, successful or not.
+    fn handle_backoff(
+        &mut self,
+        result: Result<ChangesetResponse, KintoError>,
+    ) -> Result<ChangesetResponse, ClientError> {
+        match result {
+            Ok(changeset) => {
+                self.set_backoff(changeset.backoff)?;
+                Ok(changeset)
+            }
+            Err(err) => {
+                self.set_backoff(err.response().and_then(|r| get_backoff(&r.headers)))?;
+                Err(err.into())
             }
-            self.backoff_until = None;
         }
-        Ok(())
     }
 
     pub async fn server_info(&mut self) -> Result<&Value, ClientError> {
@@ -613,12 +1330,13 @@ impl Client {
                 .get(info_url)
                 .await
                 .map_err(|_err| ClientError::APIError(KintoError::HTTPBackendError()))?;
+            self.set_backoff(get_backoff(&response.headers))?;
 
             if response.is_success() {
                 let server_info = serde_json::from_slice(&response.body).map_err(|_err| {
                     ClientError::APIError(KintoError::UnexpectedResponse {
                         url: self.server_url.clone(),
-                        response,
+                        response: Box::new(response),
                     })
                 })?;
 
@@ -627,7 +1345,7 @@ impl Client {
             } else {
                 Err(ClientError::APIError(KintoError::UnexpectedResponse {
                     url: self.server_url.clone(),
-                    response,
+                    response: Box::new(response),
                 }))
             }
         }
@@ -651,7 +1369,7 @@ impl Client {
         E: 'static + Send + Sync + std::error::Error,
     {
         match record.attachment_metadata()? {
-            None => return Ok(None),
+            None => Ok(None),
             Some(m) => Ok(Some(self.fetch_attachment_from_metadata(m).await?)),
         }
     }
@@ -673,48 +1391,32 @@ impl Client {
         T: TryFrom<Vec<u8>, Error = E>,
         E: 'static + Send + Sync + std::error::Error,
     {
-        let key = format!(
-            "attachment:{}/{}:{}",
-            self.bucket_name, self.collection_name, metadata.hash
-        );
-        let bytes = match self.storage.retrieve(&key) {
-            Ok(bytes) => Ok(bytes),
+        let key = attachment_key(&self.bucket_name, &self.collection_name, &metadata.hash);
+        let stored = if self.attachment_index()?.contains(&metadata.hash) {
+            self.storage.retrieve(&key)
+        } else {
+            Err(StorageError::KeyNotFound { key })
+        };
+        let (bytes, downloaded) = match stored {
+            Ok(bytes) => Ok((bytes, false)),
 
             Err(StorageError::KeyNotFound { .. }) => {
                 // Download the attachment
-                let url = {
-                    let server_info = self.server_info().await?;
-                    match &server_info["capabilities"]["attachments"]["base_url"] {
-                        Value::String(s) => {
-                            let full_url = format!("{}{}", s, metadata.location);
-                            Url::parse(&full_url)
-                                .map_err(|err| ClientError::APIError(KintoError::URLError(err)))?
-                        }
-                        Value::Null => {
-                            return Err(ClientError::CompatibilityError(anyhow!(
-                                "server does not support attachments"
-                            )));
-                        }
-                        _ => {
-                            return Err(ClientError::CompatibilityError(anyhow!(
-                                "server did not return a valid attachment base_url"
-                            )));
-                        }
-                    }
-                };
+                let url = self.attachment_url(metadata).await?;
 
                 let response = self
                     .http_client
                     .get(url.clone())
                     .await
                     .map_err(|_| ClientError::APIError(KintoError::HTTPBackendError()))?;
+                self.set_backoff(get_backoff(&response.headers))?;
 
                 if response.is_success() {
-                    Ok(response.body)
+                    Ok((response.body, true))
                 } else {
                     return Err(ClientError::APIError(KintoError::UnexpectedResponse {
                         url: url.to_string(),
-                        response,
+                        response: Box::new(response),
                     }));
                 }
             }
@@ -728,6 +1430,12 @@ impl Client {
         self.verifier
             .verify_sha256_hash(bytes.as_slice(), hash_bytes.as_slice())?;
 
+        if downloaded {
+            self.cache_attachment(&metadata.hash, &bytes)?;
+        } else {
+            self.mark_attachment_used(&metadata.hash);
+        }
+
         let rv = bytes
             .try_into()
             .context("parsing attachment to requested type")
@@ -735,26 +1443,479 @@ impl Client {
         Ok(rv)
     }
 
-    /// Store a record on the server.
-    ///
-    /// # Arguments
-    ///
-    /// * `record` - the record to store.
-    pub async fn store_record(&self, record: Record) -> Result<KintoObject, ClientError> {
-        put_record(
-            self.http_client.as_ref(),
-            &self.server_url,
-            self.authorization.clone(),
-            &self.bucket_name,
-            &self.collection_name,
-            record.id(),
-            &record.value,
-        )
-        .await
-        .map_err(ClientError::APIError)
+    /// Return the base URL of attachments advertised by the server.
+    async fn attachments_base_url(&mut self) -> Result<String, ClientError> {
+        let server_info = self.server_info().await?;
+        match &server_info["capabilities"]["attachments"]["base_url"] {
+            Value::String(s) => Ok(s.clone()),
+            Value::Null => Err(ClientError::CompatibilityError(anyhow!(
+                "server does not support attachments"
+            ))),
+            _ => Err(ClientError::CompatibilityError(anyhow!(
+                "server did not return a valid attachment base_url"
+            ))),
+        }
     }
 
-    /// Delete a record from the server.
+    /// Return the URL of an attachment.
+    async fn attachment_url(&mut self, metadata: &AttachmentMetadata) -> Result<Url, ClientError> {
+        let full_url = format!(
+            "{}{}",
+            self.attachments_base_url().await?,
+            metadata.location
+        );
+        Url::parse(&full_url).map_err(|err| ClientError::APIError(KintoError::URLError(err)))
+    }
+
+    /// Download the attachments bundle of the collection, and store the specified
+    /// attachments it contains.
+    ///
+    /// Return the ids of the records whose attachment was stored.
+    async fn cache_attachments_bundle(
+        &mut self,
+        attachments: &[(String, AttachmentMetadata)],
+    ) -> Result<HashSet<String>, ClientError> {
+        let full_url = format!(
+            "{}bundles/{}--{}.zip",
+            self.attachments_base_url().await?,
+            self.bucket_name,
+            self.collection_name
+        );
+        let url = Url::parse(&full_url)
+            .map_err(|err| ClientError::APIError(KintoError::URLError(err)))?;
+
+        let response = self
+            .http_client
+            .get(url.clone())
+            .await
+            .map_err(|_| ClientError::APIError(KintoError::HTTPBackendError()))?;
+        self.set_backoff(get_backoff(&response.headers))?;
+        if !response.is_success() {
+            return Err(ClientError::APIError(KintoError::UnexpectedResponse {
+                url: url.to_string(),
+                response: Box::new(response),
+            }));
+        }
+
+        let mut bundle = Bundle::new(&response.body)
+            .context("reading attachments bundle")
+            .map_err(ClientError::AttachmentMetadataError)?;
+        let mut stored = HashSet::new();
+        for (id, metadata) in attachments {
+            let bytes = match bundle.read(id, metadata.size) {
+                Ok(Some(bytes)) => bytes,
+                Ok(None) => continue,
+                Err(err) => {
+                    warn!("Ignore bundled attachment of {}: {}", id, err);
+                    continue;
+                }
+            };
+            // Invalid hashes are reported when downloading the attachment individually.
+            let hash = match hex::decode(&metadata.hash) {
+                Ok(hash) => hash,
+                Err(_) => continue,
+            };
+            if let Err(err) = self.verifier.verify_sha256_hash(&bytes, &hash) {
+                warn!("Ignore bundled attachment of {}: {}", id, err);
+                continue;
+            }
+            self.cache_attachment(&metadata.hash, &bytes)?;
+            stored.insert(id.clone());
+        }
+        debug!("Stored {} attachments from bundle {}", stored.len(), url);
+        Ok(stored)
+    }
+
+    /// Download the attachments of all records that are not stored locally yet.
+    ///
+    /// Records are obtained like with [`Client::get`]. Up to `concurrency` attachments
+    /// are downloaded in parallel. Their hash is verified before they are stored, and
+    /// the outcome for each record is reported in the returned [`AttachmentsReport`].
+    ///
+    /// If the collection metadata has the `attachment.bundle` flag, the attachments bundle
+    /// (`attachments.zip`) is downloaded first, and only the attachments missing from it
+    /// are downloaded individually.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use remote_settings_client::Client;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let mut client = Client::builder().collection_name("cid").build().unwrap();
+    /// let report = client.download_all_attachments(4).await?;
+    /// for (id, result) in report.records.iter().filter(|(_, r)| r.is_err()) {
+    ///     println!("attachment of {} could not be downloaded: {:?}", id, result);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// If an error occurs while fetching the records or the server capabilities, a
+    /// [`ClientError`] is returned.
+    pub async fn download_all_attachments(
+        &mut self,
+        concurrency: usize,
+    ) -> Result<AttachmentsReport, ClientError> {
+        let collection = self.get_collection().await?;
+        let index = self.attachment_index()?;
+        let mut report = AttachmentsReport::default();
+        let mut missing = Vec::new();
+        let mut hashes = HashMap::new();
+        for mut record in collection.records {
+            let id = record.id().to_string();
+            let metadata = match record.attachment_metadata() {
+                Ok(Some(metadata)) => metadata.clone(),
+                Ok(None) => continue,
+                Err(err) => {
+                    report.records.insert(id, Err(err));
+                    continue;
+                }
+            };
+            hashes.insert(id.clone(), metadata.hash.clone());
+            if index.contains(&metadata.hash) {
+                report
+                    .records
+                    .insert(id, Ok(AttachmentStatus::AlreadyStored));
+            } else {
+                missing.push((id, metadata));
+            }
+        }
+        if missing.is_empty() {
+            return Ok(report);
+        }
+
+        self.server_info().await?;
+        if collection.metadata["attachment"]["bundle"] == true {
+            match self.cache_attachments_bundle(&missing).await {
+                Ok(stored) => {
+                    missing.retain(|(id, _)| !stored.contains(id));
+                    for id in stored {
+                        report.records.insert(id, Ok(AttachmentStatus::Downloaded));
+                    }
+                }
+                Err(err) => warn!("Ignore attachments bundle: {}", err),
+            }
+        }
+
+        let mut downloads = Vec::new();
+        for (id, metadata) in missing {
+            let expected_hash = hex::decode(&metadata.hash)
+                .context("decoded expected hash")
+                .map_err(ClientError::AttachmentMetadataError);
+            match (self.attachment_url(&metadata).await, expected_hash) {
+                (Ok(url), Ok(expected_hash)) => downloads.push((id, metadata, url, expected_hash)),
+                (Err(err), _) | (_, Err(err)) => {
+                    report.records.insert(id, Err(err));
+                }
+            }
+        }
+
+        debug!(
+            "Download {} attachments, {} at a time.",
+            downloads.len(),
+            concurrency
+        );
+        // Attachments are stored as soon as they are received, so that only the ones
+        // being downloaded are held in memory.
+        let mut index = self.attachment_index()?;
+        let http_client = self.http_client.as_ref();
+        let verifier = self.verifier.as_ref();
+        let mut results = stream::iter(downloads)
+            .map(|(id, metadata, url, expected_hash)| async move {
+                let (backoff, result) =
+                    download_verified(http_client, verifier, url, &metadata, &expected_hash).await;
+                (id, metadata, backoff, result)
+            })
+            .buffer_unordered(concurrency.max(1));
+        while let Some((id, metadata, backoff, result)) = results.next().await {
+            set_backoff(
+                &mut self.backoff_until,
+                self.storage.as_mut(),
+                &self.server_url,
+                backoff,
+            )?;
+            let result = match result {
+                Ok(bytes) => index
+                    .store(
+                        self.storage.as_mut(),
+                        (&self.bucket_name, &self.collection_name),
+                        &metadata.hash,
+                        bytes,
+                        self.max_attachments_size,
+                    )
+                    .map(|_| AttachmentStatus::Downloaded)
+                    .map_err(ClientError::from),
+                Err(err) => Err(err),
+            };
+            report.records.insert(id, result);
+        }
+        drop(results);
+        self.save_attachment_index(&index)?;
+
+        // Attachments downloaded in this run can be evicted by the next ones.
+        for (id, result) in report.records.iter_mut() {
+            if let Ok(status @ AttachmentStatus::Downloaded) = result {
+                if !index.contains(&hashes[id]) {
+                    *status = AttachmentStatus::Evicted;
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Download the attachment of a record into the specified writer.
+    ///
+    /// Unlike [`Client::fetch_attachment_from_metadata`], the attachment is never
+    /// held entirely in memory, nor kept in the local storage. Its size and hash
+    /// are verified while it is received.
+    ///
+    /// Note that streaming depends on the HTTP client: with HTTP clients that do not
+    /// implement [`net::Requester::get_streaming`], like the `ViaductClient`, the whole
+    /// attachment is received in memory first.
+    ///
+    /// Note that on error, the content written so far should be discarded.
+    ///
+    /// # Errors
+    /// If the attachment cannot be downloaded, a [`ClientError::APIError`] is returned.
+    ///
+    /// If its size or hash do not match the metadata, a [`ClientError::IntegrityError`] is returned.
+    ///
+    /// If an error occurs while writing, a [`ClientError::StorageError`] is returned.
+    pub async fn download_attachment<W>(
+        &mut self,
+        metadata: &AttachmentMetadata,
+        writer: &mut W,
+    ) -> Result<(), ClientError>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        let expected_hash = hex::decode(&metadata.hash)
+            .context("decoded expected hash")
+            .map_err(ClientError::AttachmentMetadataError)?;
+        let url = self.attachment_url(metadata).await?;
+
+        let mut response = self
+            .http_client
+            .get_streaming(url.clone())
+            .await
+            .map_err(|_| ClientError::APIError(KintoError::HTTPBackendError()))?;
+        self.set_backoff(get_backoff(&response.headers))?;
+        if !response.is_success() {
+            return Err(ClientError::APIError(KintoError::UnexpectedResponse {
+                url: url.to_string(),
+                response: Box::new(net::Response {
+                    status: response.status,
+                    body: Vec::new(),
+                    headers: response.headers,
+                }),
+            }));
+        }
+
+        let mut hasher = Sha256::new();
+        let mut size = 0;
+        while let Some(chunk) = response
+            .body
+            .next_chunk()
+            .await
+            .map_err(|_| ClientError::APIError(KintoError::HTTPBackendError()))?
+        {
+            size += chunk.len();
+            if size > metadata.size {
+                return Err(SignatureError::MismatchError(format!(
+                    "attachment is larger than {} bytes",
+                    metadata.size
+                ))
+                .into());
+            }
+            hasher.update(&chunk);
+            writer.write_all(&chunk).await.map_err(StorageError::from)?;
+        }
+        if size != metadata.size {
+            return Err(SignatureError::MismatchError(format!(
+                "attachment has {} bytes instead of {}",
+                size, metadata.size
+            ))
+            .into());
+        }
+        writer.flush().await.map_err(StorageError::from)?;
+
+        self.verifier
+            .verify_sha256_digest(&hasher.finalize()[..], &expected_hash)?;
+        debug!("Downloaded attachment {} ({} bytes)", url, size);
+        Ok(())
+    }
+
+    /// Download the attachment of a record into the specified file.
+    ///
+    /// The file is only created once the attachment was entirely received and verified.
+    /// File operations do not block the executor, they are run in a separate thread pool.
+    /// See [`Client::download_attachment`].
+    pub async fn download_attachment_to_file<P: AsRef<Path>>(
+        &mut self,
+        metadata: &AttachmentMetadata,
+        path: P,
+    ) -> Result<(), ClientError> {
+        let path = path.as_ref();
+        // Concurrent downloads to the same path don't write into the same file.
+        let tmp_path = storage::file_storage::unique_path(path, ".part");
+
+        // File operations are blocking: run them in a thread pool.
+        let file = {
+            let tmp_path = tmp_path.clone();
+            unblock(move || std::fs::File::create(tmp_path))
+                .await
+                .map_err(StorageError::from)?
+        };
+        let mut writer = Unblock::new(file);
+        let result = self.download_attachment(metadata, &mut writer).await;
+        let file = writer.into_inner().await;
+        let result = match result {
+            Ok(()) => {
+                let (tmp_path, path) = (tmp_path.clone(), path.to_owned());
+                unblock(move || {
+                    file.sync_all()
+                        .and_then(|_| std::fs::rename(tmp_path, path))
+                })
+                .await
+                .map_err(|err| StorageError::from(err).into())
+            }
+            Err(err) => {
+                // The file must be closed before being removed on Windows.
+                drop(file);
+                Err(err)
+            }
+        };
+        if result.is_err() {
+            let _ = unblock(move || std::fs::remove_file(tmp_path)).await;
+        }
+        result
+    }
+
+    /// Remove all attachments of this collection from the local storage.
+    ///
+    /// # Errors
+    /// If an error occurs while deleting attachments, a [`ClientError::StorageError`] is returned.
+    pub fn purge_attachments(&mut self) -> Result<(), ClientError> {
+        let mut index = self.attachment_index()?;
+        let hashes = index.clear();
+        self.used_attachments.clear();
+        debug!("Purge {} attachments.", hashes.len());
+        self.delete_attachments(&hashes)?;
+        index.save(
+            self.storage.as_mut(),
+            &self.bucket_name,
+            &self.collection_name,
+        )?;
+        Ok(())
+    }
+
+    /// Load the index of the attachments stored locally, with the attachments read
+    /// since it was saved marked as used.
+    ///
+    /// The first time, the index is created from the attachments of the stored records,
+    /// that previous versions stored without index.
+    fn attachment_index(&mut self) -> Result<AttachmentIndex, StorageError> {
+        let (bid, cid) = (&self.bucket_name, &self.collection_name);
+        if let Some(mut index) = AttachmentIndex::load(self.storage.as_ref(), bid, cid) {
+            index.mark_used(&self.used_attachments);
+            return Ok(index);
+        }
+        let mut index = AttachmentIndex::default();
+        for record in self.storage.retrieve_records(&self._records_key())? {
+            if let Some(hash) = record["attachment"]["hash"].as_str() {
+                // Previous versions did not store empty attachments.
+                match self.storage.retrieve(&attachment_key(bid, cid, hash)) {
+                    Ok(bytes) if !bytes.is_empty() => index.touch(hash, bytes.len()),
+                    _ => {}
+                }
+            }
+        }
+        debug!("Index {} stored attachments.", index.len());
+        index.save(self.storage.as_mut(), bid, cid)?;
+        Ok(index)
+    }
+
+    /// Mark an attachment read from the local storage as used.
+    ///
+    /// To avoid writing into the storage on every read, it is only marked in the
+    /// index the next time the index is saved.
+    fn mark_attachment_used(&mut self, hash: &str) {
+        self.used_attachments.retain(|used| used != hash);
+        self.used_attachments.push(hash.to_string());
+    }
+
+    /// Save the index of the attachments stored locally.
+    fn save_attachment_index(&mut self, index: &AttachmentIndex) -> Result<(), StorageError> {
+        index.save(
+            self.storage.as_mut(),
+            &self.bucket_name,
+            &self.collection_name,
+        )?;
+        self.used_attachments.clear();
+        Ok(())
+    }
+
+    /// Store an attachment that was just downloaded, and mark it as used.
+    fn cache_attachment(&mut self, hash: &str, bytes: &[u8]) -> Result<(), StorageError> {
+        let mut index = self.attachment_index()?;
+        index.store(
+            self.storage.as_mut(),
+            (&self.bucket_name, &self.collection_name),
+            hash,
+            bytes.to_vec(),
+            self.max_attachments_size,
+        )?;
+        self.save_attachment_index(&index)
+    }
+
+    /// Remove the attachments that are not referenced by the specified records anymore.
+    fn prune_attachments(&mut self, records: &[Record]) -> Result<(), StorageError> {
+        let mut index = self.attachment_index()?;
+        let referenced: HashSet<&str> = records
+            .iter()
+            .filter_map(|record| record["attachment"]["hash"].as_str())
+            .collect();
+        let unused = index.retain(&referenced);
+        if unused.is_empty() && self.used_attachments.is_empty() {
+            return Ok(());
+        }
+        debug!("Remove {} unused attachments.", unused.len());
+        self.delete_attachments(&unused)?;
+        self.save_attachment_index(&index)
+    }
+
+    fn delete_attachments(&mut self, hashes: &[String]) -> Result<(), StorageError> {
+        for hash in hashes {
+            self.storage.delete(&attachment_key(
+                &self.bucket_name,
+                &self.collection_name,
+                hash,
+            ))?;
+        }
+        Ok(())
+    }
+
+    /// Store a record on the server.
+    ///
+    /// # Arguments
+    ///
+    /// * `record` - the record to store.
+    pub async fn store_record(&self, record: Record) -> Result<KintoObject, ClientError> {
+        put_record(
+            self.http_client.as_ref(),
+            &self.server_url,
+            self.authorization.clone(),
+            &self.bucket_name,
+            &self.collection_name,
+            record.id(),
+            &record.value,
+        )
+        .await
+        .map_err(ClientError::APIError)
+    }
+
+    /// Delete a record from the server.
     ///
     /// # Arguments
     ///
@@ -847,37 +2008,105 @@ impl Client {
     }
 }
 
-fn merge_changes(local_records: Vec<Record>, remote_changes: Vec<KintoObject>) -> Vec<Record> {
+/// Download an attachment and verify its hash.
+///
+/// The backoff requested by the server, if any, is returned along the result.
+async fn download_verified(
+    http_client: &(dyn net::Requester + 'static),
+    verifier: &dyn Verification,
+    url: Url,
+    metadata: &AttachmentMetadata,
+    expected_hash: &[u8],
+) -> (Option<u64>, Result<Vec<u8>, ClientError>) {
+    let response = match http_client.get(url.clone()).await {
+        Ok(response) => response,
+        Err(_) => {
+            return (
+                None,
+                Err(ClientError::APIError(KintoError::HTTPBackendError())),
+            )
+        }
+    };
+    let backoff = get_backoff(&response.headers);
+    if !response.is_success() {
+        return (
+            backoff,
+            Err(ClientError::APIError(KintoError::UnexpectedResponse {
+                url: url.to_string(),
+                response: Box::new(response),
+            })),
+        );
+    }
+    if response.body.len() != metadata.size {
+        return (
+            backoff,
+            Err(SignatureError::MismatchError(format!(
+                "attachment has {} bytes instead of {}",
+                response.body.len(),
+                metadata.size
+            ))
+            .into()),
+        );
+    }
+    let result = verifier
+        .verify_sha256_hash(&response.body, expected_hash)
+        .map(|_| response.body)
+        .map_err(ClientError::from);
+    (backoff, result)
+}
+
+fn merge_changes(
+    local_records: Vec<Record>,
+    remote_changes: Vec<KintoObject>,
+) -> (Vec<Record>, SyncResult) {
     // Merge changes by record id and delete tombstones.
     let mut local_by_id: HashMap<String, Record> = local_records
         .into_iter()
         .map(|record| (record.id().into(), record))
         .collect();
+    let mut changes = SyncResult::default();
     for entry in remote_changes.into_iter().rev() {
         let change = Record::new(entry);
         let id = change.id();
         if change.deleted() {
-            local_by_id.remove(id);
+            if let Some(old) = local_by_id.remove(id) {
+                changes.deleted.push(old);
+            }
         } else {
-            local_by_id.insert(id.into(), change);
+            match local_by_id.insert(id.into(), change.clone()) {
+                None => changes.created.push(change),
+                Some(old) if old != change => {
+                    changes.updated.push(RecordUpdate { old, new: change })
+                }
+                Some(_) => (),
+            }
         }
     }
 
-    local_by_id.into_iter().map(|(_, v)| v).collect()
+    (local_by_id.into_values().collect(), changes)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::net::{Headers, Method, Requester, TestHttpClient, TestResponse};
-    use super::signatures::{SignatureError, Verification};
+    use super::attachment_cache::tests::make_bundle;
+    use super::kinto_http::KintoError;
+    use super::net::{
+        Headers, Method, Requester, Response, RetryPolicy, TestHttpClient, TestResponse,
+    };
+    use super::signatures::certificate_cache::tests::CERTIFICATE;
+    use super::signatures::{SignatureError, Verification, VerificationMode};
     use super::{
-        Client, ClientError, Collection, DummyStorage, DummyVerifier, MemoryStorage, Record,
+        Client, ClientBuilderError, ClientError, Collection, DummyStorage, DummyVerifier,
+        Environment, FileStorage, InvalidRecordPolicy, MemoryStorage, Record, Storage,
+        StorageError, PROD_CERT_ROOT_HASH, STAGE_CERT_ROOT_HASH, STAGE_SERVER_URL,
     };
-    use crate::client::AttachmentMetadata;
+    use crate::client::{AttachmentMetadata, AttachmentStatus};
     use async_trait::async_trait;
     use env_logger;
     use httpmock::MockServer;
-    use serde_json::json;
+    use serde::Deserialize;
+    use serde_json::{json, Value};
+    use std::sync::{Arc, Mutex};
     use std::time::Duration;
 
     #[cfg(feature = "viaduct_client")]
@@ -992,7 +2221,190 @@ mod tests {
         assert!(client.sync_if_empty);
         assert!(client.trust_local);
         // And Debug format
-        assert_eq!(format!("{:?}", client), "Client { server_url: \"https://firefox.settings.services.mozilla.com/v1\", bucket_name: \"main\", collection_name: \"cid\", signer_name: \"remote-settings.content-signature.mozilla.org\", verifier: Box<dyn Verification>, storage: Box<dyn Storage>, sync_if_empty: true, trust_local: true, backoff_until: None, cert_root_hash: \"97:E8:BA:9C:F1:2F:B3:DE:53:CC:42:A4:E6:57:7E:D6:4D:F4:93:C2:47:B4:14:FE:A0:36:81:8D:38:23:56:0E\", http_client: DummyClient, server_info: None, authorization: None }");
+        assert_eq!(format!("{:?}", client), "Client { environment: Prod, server_url: \"https://firefox.settings.services.mozilla.com/v1\", bucket_name: \"main\", collection_name: \"cid\", signer_name: None, signer_resolver: None, verifier: Box<dyn Verification>, verification_mode: Insecure, revocation_checker: None, storage: Box<dyn Storage>, sync_if_empty: true, trust_local: true, initial_data: None, backoff_until: None, cert_root_hash: \"97:E8:BA:9C:F1:2F:B3:DE:53:CC:42:A4:E6:57:7E:D6:4D:F4:93:C2:47:B4:14:FE:A0:36:81:8D:38:23:56:0E\", http_client: CertificateClient { inner: ClockClient { inner: DummyClient, tolerance: 0, .. }, cache: CertificateCache { .. } }, retry_policy: RetryPolicy { max_attempts: 1, base_delay: 1s, max_delay: 60s, jitter: true, honor_retry_after: true }, http_cache: false, clock_skew_tolerance: 0ns, certificates: CertificateCache { .. }, used_attachments: [], server_info: None, authorization: None, max_attachments_size: None }");
+    }
+
+    #[test]
+    fn test_environment() {
+        let client = Client::builder()
+            .environment(Environment::Stage)
+            .collection_name("cid")
+            .build()
+            .unwrap();
+        assert_eq!(client.server_url, STAGE_SERVER_URL);
+        assert_eq!(client.cert_root_hash, STAGE_CERT_ROOT_HASH);
+
+        let client = Client::builder()
+            .environment(Environment::Custom {
+                server_url: "https://example.com/v1".to_string(),
+                cert_root_hash: "AB:CD".to_string(),
+            })
+            .collection_name("cid")
+            .build()
+            .unwrap();
+        assert_eq!(client.server_url, "https://example.com/v1");
+        assert_eq!(client.cert_root_hash, "AB:CD");
+
+        // No mismatched server and root certificate.
+        let result = Client::builder()
+            .server_url("https://example.com/v1")
+            .environment(Environment::Dev)
+            .collection_name("cid")
+            .build();
+        assert!(matches!(
+            result,
+            Err(ClientBuilderError::ValidationError(_))
+        ));
+
+        let result = Client::builder()
+            .environment(Environment::Dev)
+            .cert_root_hash(PROD_CERT_ROOT_HASH.to_string())
+            .collection_name("cid")
+            .build();
+        assert!(matches!(
+            result,
+            Err(ClientBuilderError::ValidationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_signer_name_resolution() {
+        let metadata = json!({"signature": {"signer_id": "normandy"}});
+
+        let client = Client::builder()
+            .bucket_name("security-state")
+            .collection_name("onecrl")
+            .build()
+            .unwrap();
+        assert_eq!(
+            client.signer_for(&json!({})),
+            "onecrl.content-signature.mozilla.org"
+        );
+        // The signer of the metadata is ignored.
+        assert_eq!(
+            client.signer_for(&metadata),
+            "onecrl.content-signature.mozilla.org"
+        );
+
+        let client = Client::builder()
+            .collection_name("cid")
+            .signer_resolver(Arc::new(|_: &str, cid: &str, _: &Value| {
+                Some(format!("{}.example.com", cid))
+            }))
+            .build()
+            .unwrap();
+        assert_eq!(client.signer_for(&metadata), "cid.example.com");
+
+        let client = Client::builder()
+            .collection_name("cid")
+            .signer_name("signer.example.com")
+            .signer_resolver(Arc::new(|_: &str, _: &str, _: &Value| None))
+            .build()
+            .unwrap();
+        assert_eq!(client.signer_for(&metadata), "signer.example.com");
+    }
+
+    #[tokio::test]
+    async fn test_signer_from_metadata_must_match() {
+        init();
+
+        struct AcceptingVerifier {}
+
+        #[async_trait]
+        impl Verification for AcceptingVerifier {
+            async fn verify(
+                &self,
+                _: &'_ (dyn Requester + 'static),
+                _: &Collection,
+                _: &str,
+            ) -> Result<(), SignatureError> {
+                Ok(())
+            }
+
+            fn verify_nist384p_chain(
+                &self,
+                _: u64,
+                _: &[u8],
+                _: &str,
+                _: &str,
+                _: &[u8],
+                _: &[u8],
+            ) -> Result<(), SignatureError> {
+                unreachable!()
+            }
+
+            fn verify_sha256_hash(&self, _: &[u8], _: &[u8]) -> Result<(), SignatureError> {
+                Ok(())
+            }
+        }
+
+        let mut client = Client::builder()
+            .bucket_name("security-state")
+            .collection_name("onecrl")
+            .verifier(Box::new(AcceptingVerifier {}))
+            .build()
+            .unwrap();
+        let collection = |signer_id: &str| Collection {
+            bid: "security-state".to_string(),
+            cid: "onecrl".to_string(),
+            signer: String::new(),
+            metadata: json!({"signature": {"signer_id": signer_id}}),
+            records: vec![],
+            timestamp: 42,
+        };
+
+        // A collection signed by another signer is refused, even if its metadata says so.
+        match client.verify_collection(&mut collection("normandy")).await {
+            Err(SignatureError::InvalidCertificateSubject(name)) => assert_eq!(
+                name,
+                "normandy.content-signature.mozilla.org (expected onecrl.content-signature.mozilla.org)"
+            ),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+
+        let mut onecrl = collection("onecrl");
+        client.verify_collection(&mut onecrl).await.unwrap();
+        assert_eq!(onecrl.signer, "onecrl.content-signature.mozilla.org");
+    }
+
+    #[test]
+    fn test_strict_verification_mode() {
+        let err = Client::builder()
+            .collection_name("cid")
+            .verification_mode(VerificationMode::Strict)
+            .build()
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("strict verification mode"),
+            "{}",
+            err
+        );
+
+        struct CheckingVerifier {}
+        impl Verification for CheckingVerifier {
+            fn verify_nist384p_chain(
+                &self,
+                _: u64,
+                _: &[u8],
+                _: &str,
+                _: &str,
+                _: &[u8],
+                _: &[u8],
+            ) -> Result<(), SignatureError> {
+                Ok(())
+            }
+
+            fn verify_sha256_hash(&self, _: &[u8], _: &[u8]) -> Result<(), SignatureError> {
+                Ok(())
+            }
+        }
+
+        assert!(Client::builder()
+            .collection_name("cid")
+            .verifier(Box::new(CheckingVerifier {}))
+            .verification_mode(VerificationMode::Strict)
+            .build()
+            .is_ok());
     }
 
     #[tokio::test]
@@ -1189,7 +2601,8 @@ mod tests {
             bid: "main".to_owned(),
             cid: "search-config".to_owned(),
             metadata: json!({}),
-            records: vec![Record::default()],
+            // Records without id cannot be stored.
+            records: vec![Record::new(json!({"id": "record-1"}))],
             timestamp: 42,
             signer: "some-name".to_owned(),
         };
@@ -1404,19 +2817,59 @@ mod tests {
     }
 
     #[tokio::test]
-    #[cfg(feature = "ring_verifier")]
-    async fn test_sync_uses_x5u_from_metadata_to_verify_signatures() {
+    async fn test_sync_retries_server_errors() {
         init();
 
         let mock_server = MockServer::start();
         let mut get_changeset_mock = mock_server.mock(|when, then| {
-            when.path("/buckets/main/collections/onecrl/changeset")
+            when.path("/buckets/main/collections/regions/changeset")
                 .query_param("_expected", "42");
-            then.body(
+            then.status(503).header("Retry-After", "0").body(
                 r#"{
-                    "metadata": {
-                        "missing": "x5u"
-                    },
+                    "code": 503,
+                    "errno": 201,
+                    "error": "Service Unavailable",
+                    "message": "Back soon"
+                }"#,
+            );
+        });
+
+        let mut client = Client::builder()
+            .server_url(mock_server.url(""))
+            .http_client(Box::new(ViaductClient))
+            .collection_name("regions")
+            .retry_policy(RetryPolicy {
+                max_attempts: 3,
+                base_delay: Duration::from_millis(1),
+                ..RetryPolicy::default()
+            })
+            .build()
+            .unwrap();
+
+        let err = client.sync(42).await.unwrap_err();
+        assert!(matches!(
+            err,
+            ClientError::APIError(KintoError::ServerError { .. })
+        ));
+
+        get_changeset_mock.assert_hits(3);
+        get_changeset_mock.delete();
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "ring_verifier")]
+    async fn test_sync_uses_x5u_from_metadata_to_verify_signatures() {
+        init();
+
+        let mock_server = MockServer::start();
+        let mut get_changeset_mock = mock_server.mock(|when, then| {
+            when.path("/buckets/main/collections/onecrl/changeset")
+                .query_param("_expected", "42");
+            then.body(
+                r#"{
+                    "metadata": {
+                        "missing": "x5u"
+                    },
                     "changes": [{
                         "id": "record-1",
                         "last_modified": 13,
@@ -1431,7 +2884,7 @@ mod tests {
             .server_url(mock_server.url(""))
             .http_client(Box::new(ViaductClient))
             .collection_name("onecrl")
-            .verifier(Box::new(RingVerifier {}))
+            .verifier(Box::new(RingVerifier::new()))
             .build()
             .unwrap();
 
@@ -1565,6 +3018,716 @@ mod tests {
         get_changeset_mock_2.delete();
     }
 
+    #[tokio::test]
+    async fn test_sync_with_changes_lists_changed_records() {
+        init();
+
+        let fake_server = "https://www.example.com/v1";
+        let test_responses = [
+            (
+                "_expected=15",
+                json!({
+                    "metadata": {},
+                    "changes": [
+                        {"id": "record-1", "last_modified": 15},
+                        {"id": "record-2", "last_modified": 14},
+                        {"id": "record-3", "last_modified": 13}
+                    ],
+                    "timestamp": 15
+                }),
+            ),
+            (
+                "_expected=42&_since=%2215%22",
+                json!({
+                    "metadata": {},
+                    "changes": [
+                        {"id": "record-4", "last_modified": 42},
+                        {"id": "record-2", "last_modified": 30, "deleted": true},
+                        {"id": "record-1", "last_modified": 20, "field": "after"},
+                        {"id": "record-5", "last_modified": 19, "deleted": true}
+                    ],
+                    "timestamp": 42
+                }),
+            ),
+        ]
+        .iter()
+        .map(|(query, body)| TestResponse {
+            request_method: Method::GET,
+            request_url: format!(
+                "{}/buckets/main/collections/onecrl/changeset?{}",
+                fake_server, query
+            ),
+            response_status: 200,
+            response_body: body.to_string().as_bytes().to_vec(),
+            response_headers: Headers::new(),
+        })
+        .collect();
+
+        let mut client = Client::builder()
+            .server_url(fake_server)
+            .http_client(Box::new(TestHttpClient::new(test_responses)))
+            .collection_name("onecrl")
+            .storage(Box::new(MemoryStorage::new()))
+            .build()
+            .unwrap();
+
+        let res = client.sync_with_changes(15).await.unwrap();
+        assert_eq!(res.created.len(), 3);
+        assert!(res.updated.is_empty());
+        assert!(res.deleted.is_empty());
+
+        let res = client.sync_with_changes(42).await.unwrap();
+        assert_eq!(res.collection.records.len(), 3);
+        assert_eq!(res.created.len(), 1);
+        assert_eq!(res.created[0].id(), "record-4");
+        assert_eq!(res.updated.len(), 1);
+        assert_eq!(res.updated[0].old["field"], serde_json::Value::Null);
+        assert_eq!(res.updated[0].new["field"].as_str(), Some("after"));
+        assert_eq!(res.deleted.len(), 1);
+        assert_eq!(res.deleted[0].id(), "record-2");
+
+        // Already up-to-date.
+        let res = client.sync_with_changes(42).await.unwrap();
+        assert_eq!(res.collection.records.len(), 3);
+        assert!(res.created.is_empty() && res.updated.is_empty() && res.deleted.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_http_cache_revalidates_changesets() {
+        init();
+
+        /// Respond to conditional requests with `304 Not Modified`.
+        #[derive(Debug)]
+        struct NotModifiedClient {
+            statuses: Arc<Mutex<Vec<(String, u16)>>>,
+        }
+
+        #[async_trait]
+        impl Requester for NotModifiedClient {
+            async fn get(&self, url: url::Url) -> Result<Response, ()> {
+                self.request_json(Method::GET, url, vec![], Headers::new())
+                    .await
+            }
+
+            async fn request_json(
+                &self,
+                _method: Method,
+                url: url::Url,
+                _data: Vec<u8>,
+                headers: Headers,
+            ) -> Result<Response, ()> {
+                let (status, body) = if headers.get("If-None-Match") == Some(&"\"42\"".into()) {
+                    (304, vec![])
+                } else {
+                    let collection = url.path().split('/').nth(5).unwrap();
+                    let change = if collection == "changes" {
+                        json!({"id": "a", "last_modified": 42, "bucket": "main", "collection": "cid"})
+                    } else {
+                        json!({"id": "record-1", "last_modified": 42})
+                    };
+                    let body = json!({"metadata": {}, "changes": [change], "timestamp": 42});
+                    (200, body.to_string().into_bytes())
+                };
+                self.statuses
+                    .lock()
+                    .unwrap()
+                    .push((url.path().to_string(), status));
+                let mut headers = Headers::new();
+                headers.insert("ETag".into(), "\"42\"".into());
+                Ok(Response {
+                    status,
+                    body,
+                    headers,
+                })
+            }
+        }
+
+        let statuses = Arc::new(Mutex::new(Vec::new()));
+        let mut client = Client::builder()
+            .server_url("https://www.example.com/v1")
+            .http_client(Box::new(NotModifiedClient {
+                statuses: statuses.clone(),
+            }))
+            .collection_name("cid")
+            .storage(Box::new(MemoryStorage::new()))
+            .http_cache(true)
+            .build()
+            .unwrap();
+
+        client.sync(None).await.unwrap();
+        client.sync(None).await.unwrap();
+        // The cached content is used when the server has no changes.
+        client.storage = Box::new(MemoryStorage::new());
+        let records = client.sync(None).await.unwrap().records;
+        assert_eq!(records.len(), 1);
+
+        assert_eq!(
+            *statuses.lock().unwrap(),
+            vec![
+                (
+                    "/v1/buckets/monitor/collections/changes/changeset".into(),
+                    200
+                ),
+                ("/v1/buckets/main/collections/cid/changeset".into(), 200),
+                (
+                    "/v1/buckets/monitor/collections/changes/changeset".into(),
+                    304
+                ),
+                (
+                    "/v1/buckets/monitor/collections/changes/changeset".into(),
+                    304
+                ),
+                ("/v1/buckets/main/collections/cid/changeset".into(), 304),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_certificate_chains_are_cached() {
+        init();
+
+        /// Serve a signed collection, and count the downloads of its certificate chain.
+        #[derive(Debug)]
+        struct SignedCollectionClient {
+            x5u_requests: Arc<Mutex<u32>>,
+        }
+
+        #[async_trait]
+        impl Requester for SignedCollectionClient {
+            async fn get(&self, url: url::Url) -> Result<Response, ()> {
+                self.request_json(Method::GET, url, vec![], Headers::new())
+                    .await
+            }
+
+            async fn request_json(
+                &self,
+                _method: Method,
+                url: url::Url,
+                _data: Vec<u8>,
+                _headers: Headers,
+            ) -> Result<Response, ()> {
+                let body = match url.path() {
+                    "/v1/x5u" => {
+                        *self.x5u_requests.lock().unwrap() += 1;
+                        CERTIFICATE.as_bytes().to_vec()
+                    }
+                    "/v1/buckets/monitor/collections/changes/changeset" => json!({
+                        "metadata": {},
+                        "changes": [{"id": "a", "last_modified": 42, "bucket": "main", "collection": "cid"}],
+                        "timestamp": 42
+                    })
+                    .to_string()
+                    .into_bytes(),
+                    _ => json!({
+                        "metadata": {"signature": {"x5u": "https://www.example.com/v1/x5u"}},
+                        "changes": [{"id": "record-1", "last_modified": 42}],
+                        "timestamp": 42
+                    })
+                    .to_string()
+                    .into_bytes(),
+                };
+                Ok(Response {
+                    status: 200,
+                    body,
+                    headers: Headers::new(),
+                })
+            }
+        }
+
+        struct AcceptingVerifier {}
+
+        impl Verification for AcceptingVerifier {
+            fn verify_nist384p_chain(
+                &self,
+                _: u64,
+                pem_bytes: &[u8],
+                _: &str,
+                _: &str,
+                _: &[u8],
+                _: &[u8],
+            ) -> Result<(), SignatureError> {
+                assert_eq!(pem_bytes, CERTIFICATE.as_bytes());
+                Ok(())
+            }
+
+            fn verify_sha256_hash(&self, _: &[u8], _: &[u8]) -> Result<(), SignatureError> {
+                Ok(())
+            }
+        }
+
+        let x5u_requests = Arc::new(Mutex::new(0));
+        let mut client = Client::builder()
+            .server_url("https://www.example.com/v1")
+            .http_client(Box::new(SignedCollectionClient {
+                x5u_requests: x5u_requests.clone(),
+            }))
+            .collection_name("cid")
+            .signer_name("test")
+            .verifier(Box::new(AcceptingVerifier {}))
+            .storage(Box::new(MemoryStorage::new()))
+            .build()
+            .unwrap();
+
+        client.sync(None).await.unwrap();
+        // The local data is verified again with the cached chain.
+        client.sync(None).await.unwrap();
+        assert_eq!(*x5u_requests.lock().unwrap(), 1);
+        assert!(client.storage.retrieve("certificates").is_ok());
+    }
+
+    /// A storage that logs the calls of records methods.
+    struct RecordingStorage {
+        inner: MemoryStorage,
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Storage for RecordingStorage {
+        fn store(&mut self, key: &str, value: Vec<u8>) -> Result<(), StorageError> {
+            self.calls.lock().unwrap().push(format!("store {}", key));
+            self.inner.store(key, value)
+        }
+
+        fn retrieve(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+            self.inner.retrieve(key)
+        }
+
+        fn update_records(
+            &mut self,
+            collection: &str,
+            upserted: Vec<serde_json::Value>,
+            deleted: &[&str],
+        ) -> Result<(), StorageError> {
+            let ids: Vec<&str> = upserted.iter().map(|r| r["id"].as_str().unwrap()).collect();
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("update {} {:?} {:?}", collection, ids, deleted));
+            self.inner.update_records(collection, upserted, deleted)
+        }
+
+        fn retrieve_records(
+            &self,
+            collection: &str,
+        ) -> Result<Vec<serde_json::Value>, StorageError> {
+            self.inner.retrieve_records(collection)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sync_only_stores_changes() {
+        init();
+
+        let fake_server = "https://www.example.com/v1";
+        let test_responses = [
+            (
+                "_expected=15",
+                json!({
+                    "metadata": {},
+                    "changes": [
+                        {"id": "record-1", "last_modified": 15},
+                        {"id": "record-2", "last_modified": 14}
+                    ],
+                    "timestamp": 15
+                }),
+            ),
+            (
+                "_expected=42&_since=%2215%22",
+                json!({
+                    "metadata": {"signature": {}},
+                    "changes": [
+                        {"id": "record-3", "last_modified": 42},
+                        {"id": "record-2", "last_modified": 30, "deleted": true}
+                    ],
+                    "timestamp": 42
+                }),
+            ),
+        ]
+        .iter()
+        .map(|(query, body)| TestResponse {
+            request_method: Method::GET,
+            request_url: format!(
+                "{}/buckets/main/collections/regions/changeset?{}",
+                fake_server, query
+            ),
+            response_status: 200,
+            response_body: body.to_string().as_bytes().to_vec(),
+            response_headers: Headers::new(),
+        })
+        .collect();
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut client = Client::builder()
+            .server_url(fake_server)
+            .http_client(Box::new(TestHttpClient::new(test_responses)))
+            .collection_name("regions")
+            .storage(Box::new(RecordingStorage {
+                inner: MemoryStorage::new(),
+                calls: calls.clone(),
+            }))
+            .build()
+            .unwrap();
+
+        client.sync(15).await.unwrap();
+        calls.lock().unwrap().clear();
+
+        let collection = client.sync(42).await.unwrap();
+        assert_eq!(collection.records.len(), 2);
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                "update main/regions [\"record-3\"] [\"record-2\"]",
+                "store main/regions:metadata",
+                "store main/regions:timestamp",
+            ]
+        );
+
+        let mut ids: Vec<String> = client
+            .get()
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| r.id().to_string())
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec!["record-1", "record-3"]);
+    }
+
+    #[tokio::test]
+    async fn test_get_migrates_stored_collection() {
+        init();
+
+        let mut client = Client::builder()
+            .collection_name("regions")
+            .storage(Box::new(MemoryStorage::new()))
+            .sync_if_empty(false)
+            .build()
+            .unwrap();
+
+        let collection = Collection {
+            bid: "main".to_owned(),
+            cid: "regions".to_owned(),
+            metadata: json!({"signature": {}}),
+            records: vec![Record::new(json!({"id": "record-1", "last_modified": 42}))],
+            timestamp: 42,
+            signer: "some-name".to_owned(),
+        };
+        client
+            .storage
+            .store(
+                "main/regions:collection",
+                serde_json::to_vec(&collection).unwrap(),
+            )
+            .unwrap();
+
+        assert_eq!(client.get().await.unwrap(), collection.records);
+        assert_eq!(
+            client.storage.retrieve("main/regions:timestamp").unwrap(),
+            b"42"
+        );
+        assert_eq!(
+            client.storage.retrieve_records("main/regions").unwrap(),
+            vec![json!({"id": "record-1", "last_modified": 42})]
+        );
+        assert!(matches!(
+            client.storage.retrieve("main/regions:collection"),
+            Err(StorageError::KeyNotFound { .. })
+        ));
+        // Records are now read from records storage.
+        assert_eq!(client.get().await.unwrap(), collection.records);
+    }
+
+    fn initial_data() -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "bid": "main",
+            "cid": "regions",
+            "metadata": {},
+            "records": [
+                {"id": "record-1", "last_modified": 15},
+                {"id": "record-2", "last_modified": 14}
+            ],
+            "timestamp": 15
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_get_returns_initial_data() {
+        init();
+
+        let dump_path = std::env::temp_dir().join("test_get_returns_initial_data.json");
+        std::fs::write(&dump_path, initial_data()).unwrap();
+
+        // No HTTP request is made.
+        let mut client = Client::builder()
+            .collection_name("regions")
+            .storage(Box::new(MemoryStorage::new()))
+            .initial_data(dump_path.as_path())
+            .build()
+            .unwrap();
+
+        assert_eq!(client.get().await.unwrap().len(), 2);
+        std::fs::remove_file(&dump_path).unwrap();
+        // Data was imported into storage.
+        assert_eq!(client.get().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_sync_from_initial_data() {
+        init();
+
+        let fake_server = "https://www.example.com/v1";
+        let mut client = Client::builder()
+            .server_url(fake_server)
+            .http_client(Box::new(TestHttpClient::new(vec![TestResponse {
+                request_method: Method::GET,
+                request_url: format!(
+                    "{}/buckets/main/collections/regions/changeset?_expected=42&_since=%2215%22",
+                    fake_server
+                ),
+                response_status: 200,
+                response_body: json!({
+                    "metadata": {},
+                    "changes": [{"id": "record-3", "last_modified": 42}],
+                    "timestamp": 42
+                })
+                .to_string()
+                .into_bytes(),
+                response_headers: Headers::new(),
+            }])))
+            .collection_name("regions")
+            .storage(Box::new(MemoryStorage::new()))
+            .initial_data(initial_data())
+            .build()
+            .unwrap();
+
+        let res = client.sync_with_changes(42).await.unwrap();
+        assert_eq!(res.collection.records.len(), 3);
+        assert_eq!(res.created.len(), 1);
+        assert_eq!(res.created[0].id(), "record-3");
+    }
+
+    #[tokio::test]
+    async fn test_initial_data_is_verified() {
+        init();
+
+        let mut client = Client::builder()
+            .collection_name("regions")
+            .storage(Box::new(MemoryStorage::new()))
+            .verifier(Box::new(VerifierWithInvalidSignatureError {}))
+            .initial_data(initial_data())
+            .sync_if_empty(false)
+            .build()
+            .unwrap();
+
+        let err = client.get().await.unwrap_err();
+        assert!(matches!(err, ClientError::IntegrityError(_)));
+
+        let mut client = Client::builder()
+            .collection_name("cid")
+            .initial_data(initial_data())
+            .sync_if_empty(false)
+            .build()
+            .unwrap();
+
+        let err = client.get().await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "initial data could not be loaded: dump of main/regions cannot be used for main/cid"
+        );
+
+        // Data is synchronized instead of failing.
+        let fake_server = "https://www.example.com/v1";
+        let test_responses = vec![
+            TestResponse {
+                request_method: Method::GET,
+                request_url: format!(
+                    "{}/buckets/monitor/collections/changes/changeset?_expected=0",
+                    fake_server
+                ),
+                response_status: 200,
+                response_body: json!({
+                    "metadata": {},
+                    "changes": [{
+                        "id": "not-read",
+                        "last_modified": 42,
+                        "bucket": "main",
+                        "collection": "cid"
+                    }],
+                    "timestamp": 42
+                })
+                .to_string()
+                .into_bytes(),
+                response_headers: Headers::new(),
+            },
+            TestResponse {
+                request_method: Method::GET,
+                request_url: format!(
+                    "{}/buckets/main/collections/cid/changeset?_expected=42",
+                    fake_server
+                ),
+                response_status: 200,
+                response_body: json!({
+                    "metadata": {},
+                    "changes": [{"id": "record-1", "last_modified": 42}],
+                    "timestamp": 42
+                })
+                .to_string()
+                .into_bytes(),
+                response_headers: Headers::new(),
+            },
+        ];
+        let mut client = Client::builder()
+            .server_url(fake_server)
+            .http_client(Box::new(TestHttpClient::new(test_responses)))
+            .collection_name("cid")
+            .storage(Box::new(MemoryStorage::new()))
+            .initial_data(initial_data())
+            .build()
+            .unwrap();
+
+        let records = client.get().await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].id(), "record-1");
+    }
+
+    #[tokio::test]
+    async fn test_initial_data_is_imported_once() {
+        init();
+
+        /// Reject the initial data, and count how many times it was verified.
+        #[derive(Default)]
+        struct InitialDataVerifier {
+            imports: Arc<Mutex<u32>>,
+        }
+
+        #[async_trait]
+        impl Verification for InitialDataVerifier {
+            fn verify_nist384p_chain(
+                &self,
+                _: u64,
+                _: &[u8],
+                _: &str,
+                _: &str,
+                _: &[u8],
+                _: &[u8],
+            ) -> Result<(), SignatureError> {
+                unreachable!()
+            }
+
+            async fn verify(
+                &self,
+                _requester: &'_ (dyn Requester + 'static),
+                collection: &Collection,
+                _: &str,
+            ) -> Result<(), SignatureError> {
+                if collection.timestamp != 15 {
+                    return Ok(());
+                }
+                *self.imports.lock().unwrap() += 1;
+                Err(SignatureError::MismatchError(
+                    "fake invalid signature".to_owned(),
+                ))
+            }
+
+            fn verify_sha256_hash(&self, _: &[u8], _: &[u8]) -> Result<(), SignatureError> {
+                Ok(())
+            }
+        }
+
+        let fake_server = "https://www.example.com/v1";
+        let test_responses = vec![
+            TestResponse {
+                request_method: Method::GET,
+                request_url: format!(
+                    "{}/buckets/monitor/collections/changes/changeset?_expected=0",
+                    fake_server
+                ),
+                response_status: 200,
+                response_body: json!({
+                    "metadata": {},
+                    "changes": [{
+                        "id": "not-read",
+                        "last_modified": 42,
+                        "bucket": "main",
+                        "collection": "regions"
+                    }],
+                    "timestamp": 42
+                })
+                .to_string()
+                .into_bytes(),
+                response_headers: Headers::new(),
+            },
+            TestResponse {
+                request_method: Method::GET,
+                request_url: format!(
+                    "{}/buckets/main/collections/regions/changeset?_expected=42",
+                    fake_server
+                ),
+                response_status: 200,
+                response_body: json!({
+                    "metadata": {},
+                    "changes": [{"id": "record-3", "last_modified": 42}],
+                    "timestamp": 42
+                })
+                .to_string()
+                .into_bytes(),
+                response_headers: Headers::new(),
+            },
+        ];
+        let verifier = InitialDataVerifier::default();
+        let imports = verifier.imports.clone();
+        let mut client = Client::builder()
+            .server_url(fake_server)
+            .http_client(Box::new(TestHttpClient::new(test_responses)))
+            .collection_name("regions")
+            .storage(Box::new(MemoryStorage::new()))
+            .verifier(Box::new(verifier))
+            .initial_data(initial_data())
+            .build()
+            .unwrap();
+
+        let records = client.get().await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].id(), "record-3");
+        assert_eq!(*imports.lock().unwrap(), 1);
+    }
+
+    #[cfg(feature = "jexl_filter")]
+    #[tokio::test]
+    async fn test_get_filtered() {
+        init();
+
+        let dump = json!({
+            "metadata": {},
+            "records": [
+                {"id": "no-filter", "last_modified": 15},
+                {"id": "empty", "last_modified": 15, "filter_expression": ""},
+                {"id": "match", "last_modified": 15, "filter_expression": "env.locale == 'fr-FR'"},
+                {"id": "no-match", "last_modified": 15, "filter_expression": "env.locale == 'en-US'"},
+                {"id": "invalid", "last_modified": 15, "filter_expression": "env.locale =="},
+                {"id": "unknown-transform", "last_modified": 15, "filter_expression": "env.locale|stableSample(0.5)"},
+                {"id": "unknown-operator", "last_modified": 15, "filter_expression": "env.locale =~ 'fr'"}
+            ],
+            "timestamp": 15
+        });
+        let mut client = Client::builder()
+            .collection_name("regions")
+            .storage(Box::new(MemoryStorage::new()))
+            .initial_data(serde_json::to_vec(&dump).unwrap())
+            .build()
+            .unwrap();
+
+        let mut ids: Vec<String> = client
+            .get_filtered(&json!({"env": {"locale": "fr-FR"}}))
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| r.id().to_string())
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec!["empty", "match", "no-filter"]);
+    }
+
     #[test]
     fn test_record_fields() {
         let r = Record::new(json!({
@@ -1589,19 +3752,108 @@ mod tests {
         assert_eq!(r.get("pi").unwrap().as_f64(), None);
         assert_eq!(r.get("foo").unwrap().get("bar").unwrap().as_u64(), Some(42));
 
-        let r = Record::new(json!({
-            "id": "abc",
-            "last_modified": 100,
-            "deleted": true
-        }));
-        assert!(r.deleted());
+        let r = Record::new(json!({
+            "id": "abc",
+            "last_modified": 100,
+            "deleted": true
+        }));
+        assert!(r.deleted());
+
+        let r = Record::new(json!({
+            "id": "abc",
+            "last_modified": 100,
+            "deleted": "foo"
+        }));
+        assert!(!r.deleted());
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Entry {
+        id: String,
+        url: String,
+    }
+
+    #[test]
+    fn test_record_deserialize() {
+        let r = Record::new(json!({
+            "id": "abc",
+            "last_modified": 100,
+            "url": "https://mozilla.org"
+        }));
+        assert_eq!(
+            r.deserialize::<Entry>().unwrap(),
+            Entry {
+                id: "abc".to_string(),
+                url: "https://mozilla.org".to_string(),
+            }
+        );
+
+        let r = Record::new(json!({
+            "id": "abc",
+            "last_modified": 100,
+            "url": 42
+        }));
+        let err = r.deserialize::<Entry>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "record abc could not be deserialized: invalid type: integer `42`, expected a string"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_typed() {
+        init();
+
+        let mut client = Client::builder()
+            .collection_name("entries")
+            .storage(Box::new(MemoryStorage::new()))
+            .build()
+            .unwrap();
+
+        let collection = Collection {
+            bid: "main".to_owned(),
+            cid: "entries".to_owned(),
+            metadata: json!({}),
+            records: vec![
+                Record::new(json!({"id": "a", "last_modified": 1, "url": "https://a.org"})),
+                Record::new(json!({"id": "b", "last_modified": 2})),
+                Record::new(json!({"id": "c", "last_modified": 3, "url": "https://c.org"})),
+            ],
+            timestamp: 3,
+            signer: "some-name".to_owned(),
+        };
+        let collection_bytes: Vec<u8> = serde_json::to_string(&collection).unwrap().into();
+        client
+            .storage
+            .store("main/entries:collection", collection_bytes)
+            .unwrap();
+
+        let typed = client
+            .get_typed::<Entry>(InvalidRecordPolicy::Skip)
+            .await
+            .unwrap();
+        assert_eq!(typed.records.len(), 2);
+        assert!(typed.invalid.is_empty());
 
-        let r = Record::new(json!({
-            "id": "abc",
-            "last_modified": 100,
-            "deleted": "foo"
-        }));
-        assert!(!r.deleted());
+        let typed = client
+            .get_typed::<Entry>(InvalidRecordPolicy::Collect)
+            .await
+            .unwrap();
+        assert_eq!(typed.records.len(), 2);
+        assert_eq!(typed.invalid.len(), 1);
+        assert!(matches!(
+            &typed.invalid[0],
+            ClientError::DeserializationError { id, .. } if id == "b"
+        ));
+
+        let err = client
+            .get_typed::<Entry>(InvalidRecordPolicy::FailFast)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "record b could not be deserialized: missing field `url`"
+        );
     }
 
     #[tokio::test]
@@ -1657,6 +3909,52 @@ mod tests {
         assert!(matches!(second_sync, ClientError::BackoffError(_)));
     }
 
+    #[tokio::test]
+    async fn test_backoff_is_persisted() {
+        init();
+
+        let mock_server = MockServer::start();
+        let mut get_changeset_mock = mock_server.mock(|when, then| {
+            when.path("/buckets/main/collections/persisted-backoff/changeset");
+            then.status(503).header("Backoff", "300").body(
+                r#"{
+                    "code": 503,
+                    "errno": 201,
+                    "error": "Service Unavailable",
+                    "message": "Back soon"
+                }"#,
+            );
+        });
+
+        let folder = std::env::temp_dir().join("test_backoff_is_persisted");
+        let _ = std::fs::remove_dir_all(&folder);
+        std::fs::create_dir_all(&folder).unwrap();
+        let new_client = || {
+            Client::builder()
+                .server_url(mock_server.url(""))
+                .http_client(Box::new(ViaductClient))
+                .collection_name("persisted-backoff")
+                .storage(Box::new(FileStorage {
+                    folder: folder.clone(),
+                    ..FileStorage::default()
+                }))
+                .build()
+                .unwrap()
+        };
+
+        // Error responses are also taken into account.
+        let err = new_client().sync(42).await.unwrap_err();
+        assert!(matches!(err, ClientError::APIError(_)));
+
+        // Another instance is also backing off.
+        let err = new_client().sync(42).await.unwrap_err();
+        assert!(matches!(err, ClientError::BackoffError(_)));
+
+        get_changeset_mock.assert_hits(1);
+        get_changeset_mock.delete();
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
     #[tokio::test]
     async fn test_attachment() {
         init();
@@ -1793,7 +4091,16 @@ mod tests {
             #[cfg(feature = "ring")]
             {
                 client_builder = client_builder.verifier(Box::new(HashOnlyVerifier {
-                    inner: Box::new(crate::client::signatures::ring_verifier::RingVerifier {}),
+                    inner: Box::new(crate::client::signatures::ring_verifier::RingVerifier::new()),
+                }));
+            }
+        } else if cfg!(feature = "rustcrypto_verifier") {
+            #[cfg(feature = "rustcrypto_verifier")]
+            {
+                client_builder = client_builder.verifier(Box::new(HashOnlyVerifier {
+                    inner: Box::new(
+                        crate::client::signatures::rustcrypto_verifier::RustCryptoVerifier::new(),
+                    ),
                 }));
             }
         } else if cfg!(feature = "rc_crypto") {
@@ -1831,6 +4138,614 @@ mod tests {
         assert_eq!(attachment_body, Some("test\n".as_bytes().to_vec()));
     }
 
+    #[tokio::test]
+    async fn test_attachments_cache() {
+        init();
+
+        let fake_server = "https://www.example.com/v1";
+        let attachment = |hash: &str| {
+            json!({
+                "hash": hash,
+                "size": 5,
+                "filename": "file.txt",
+                "location": hash,
+                "mimetype": "text/plain",
+            })
+        };
+        let test_responses: Vec<TestResponse> = vec![
+            (
+                fake_server.to_string(),
+                json!({
+                    "capabilities": {
+                        "attachments": {"base_url": format!("{}/attachments/", fake_server)}
+                    }
+                }),
+            ),
+            (
+                format!(
+                    "{}/buckets/main/collections/cached/changeset?_expected=10",
+                    fake_server
+                ),
+                json!({
+                    "metadata": {},
+                    "changes": [
+                        {"id": "r1", "last_modified": 10, "attachment": attachment("aa")},
+                        {"id": "r2", "last_modified": 9, "attachment": attachment("bb")}
+                    ],
+                    "timestamp": 10
+                }),
+            ),
+            (
+                format!(
+                    "{}/buckets/main/collections/cached/changeset?_expected=20&_since=%2210%22",
+                    fake_server
+                ),
+                json!({
+                    "metadata": {},
+                    "changes": [
+                        {"id": "r2", "last_modified": 20, "deleted": true},
+                        {"id": "r1", "last_modified": 19, "attachment": attachment("cc")}
+                    ],
+                    "timestamp": 20
+                }),
+            ),
+        ]
+        .into_iter()
+        .map(|(url, body)| (url, body.to_string().into_bytes()))
+        .chain(["aa", "bb", "cc"].iter().map(|hash| {
+            (
+                format!("{}/attachments/{}", fake_server, hash),
+                hash[..1].repeat(5).into_bytes(),
+            )
+        }))
+        .map(|(url, body)| TestResponse {
+            request_method: Method::GET,
+            request_url: url,
+            response_status: 200,
+            response_body: body,
+            response_headers: Headers::new(),
+        })
+        .collect();
+
+        let mut client = Client::builder()
+            .server_url(fake_server)
+            .http_client(Box::new(TestHttpClient::new(test_responses)))
+            .collection_name("cached")
+            .storage(Box::new(MemoryStorage::new()))
+            .max_attachments_size(12)
+            .build()
+            .unwrap();
+        let is_cached = |client: &Client, hash: &str| {
+            client
+                .storage
+                .retrieve(&format!("attachment:main/cached:{}", hash))
+                .is_ok()
+        };
+
+        let mut records = client.sync(10).await.unwrap().records;
+        records.sort_by_key(|r| r.id().to_string());
+        for record in records.iter_mut() {
+            let body: Option<Vec<u8>> = client.fetch_attachment(record).await.unwrap();
+            assert!(body.is_some());
+        }
+        assert!(is_cached(&client, "aa") && is_cached(&client, "bb"));
+
+        // Attachments of updated and deleted records are removed.
+        client.sync(20).await.unwrap();
+        assert!(!is_cached(&client, "aa") && !is_cached(&client, "bb"));
+
+        // Least recently used attachments are removed when the maximum size is exceeded.
+        for hash in &["aa", "bb", "cc", "aa"] {
+            let metadata: AttachmentMetadata = serde_json::from_value(attachment(hash)).unwrap();
+            let _: Vec<u8> = client
+                .fetch_attachment_from_metadata(&metadata)
+                .await
+                .unwrap();
+        }
+        assert!(!is_cached(&client, "bb"));
+        assert!(is_cached(&client, "aa") && is_cached(&client, "cc"));
+
+        client.purge_attachments().unwrap();
+        assert!(!is_cached(&client, "aa") && !is_cached(&client, "cc"));
+    }
+
+    #[tokio::test]
+    async fn test_attachments_index() {
+        init();
+
+        let fake_server = "https://www.example.com/v1";
+        // SHA-256 of an empty attachment.
+        let empty_hash = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+        let attachment = |hash: &str, size: usize| {
+            json!({
+                "hash": hash,
+                "size": size,
+                "filename": "file.txt",
+                "location": hash,
+                "mimetype": "text/plain",
+            })
+        };
+        let test_responses: Vec<TestResponse> = vec![
+            (
+                fake_server.to_string(),
+                json!({
+                    "capabilities": {
+                        "attachments": {"base_url": format!("{}/attachments/", fake_server)}
+                    }
+                })
+                .to_string()
+                .into_bytes(),
+            ),
+            (
+                format!(
+                    "{}/buckets/main/collections/cached/changeset?_expected=10",
+                    fake_server
+                ),
+                json!({
+                    "metadata": {},
+                    "changes": [
+                        {"id": "r1", "last_modified": 10, "attachment": attachment("aa", 5)},
+                        {"id": "r2", "last_modified": 9, "attachment": attachment(empty_hash, 0)}
+                    ],
+                    "timestamp": 10
+                })
+                .to_string()
+                .into_bytes(),
+            ),
+            (
+                format!(
+                    "{}/buckets/main/collections/cached/changeset?_expected=20&_since=%2210%22",
+                    fake_server
+                ),
+                json!({
+                    "metadata": {},
+                    "changes": [{"id": "r1", "last_modified": 20, "deleted": true}],
+                    "timestamp": 20
+                })
+                .to_string()
+                .into_bytes(),
+            ),
+            (format!("{}/attachments/aa", fake_server), b"aaaaa".to_vec()),
+            (
+                format!("{}/attachments/{}", fake_server, empty_hash),
+                vec![],
+            ),
+        ]
+        .into_iter()
+        .map(|(url, body)| TestResponse {
+            request_method: Method::GET,
+            request_url: url,
+            response_status: 200,
+            response_body: body,
+            response_headers: Headers::new(),
+        })
+        .collect();
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut client = Client::builder()
+            .server_url(fake_server)
+            .http_client(Box::new(TestHttpClient::new(test_responses)))
+            .collection_name("cached")
+            .storage(Box::new(RecordingStorage {
+                inner: MemoryStorage::new(),
+                calls: calls.clone(),
+            }))
+            .build()
+            .unwrap();
+
+        let mut records = client.sync(10).await.unwrap().records;
+        records.sort_by_key(|r| r.id().to_string());
+        for record in records.iter_mut() {
+            let body: Option<Vec<u8>> = client.fetch_attachment(record).await.unwrap();
+            assert!(body.is_some());
+        }
+
+        // Attachments read from the cache, even empty ones, are neither downloaded
+        // again nor cause writes into the storage.
+        calls.lock().unwrap().clear();
+        for record in records.iter_mut() {
+            let body: Option<Vec<u8>> = client.fetch_attachment(record).await.unwrap();
+            assert!(body.is_some());
+        }
+        assert!(calls.lock().unwrap().is_empty());
+
+        // Attachments stored before the index existed are indexed, and removed once
+        // their record is deleted.
+        client.storage.delete("attachments:main/cached").unwrap();
+        client.sync(20).await.unwrap();
+        assert!(matches!(
+            client
+                .storage
+                .retrieve("attachment:main/cached:aa")
+                .as_deref(),
+            Err(StorageError::KeyNotFound { .. }) | Ok([])
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_download_all_attachments() {
+        init();
+
+        let fake_server = "https://www.example.com/v1";
+        let attachment = |hash: &str| {
+            json!({
+                "hash": hash,
+                "size": 5,
+                "filename": "file.txt",
+                "location": hash,
+                "mimetype": "text/plain",
+            })
+        };
+        let test_responses = || -> Vec<TestResponse> {
+            vec![
+                (
+                    fake_server.to_string(),
+                    json!({
+                        "capabilities": {
+                            "attachments": {"base_url": format!("{}/attachments/", fake_server)}
+                        }
+                    }),
+                ),
+                (
+                    format!(
+                        "{}/buckets/main/collections/prefetch/changeset?_expected=10",
+                        fake_server
+                    ),
+                    json!({
+                        "metadata": {},
+                        "changes": [
+                            {"id": "r1", "last_modified": 10, "attachment": attachment("aa")},
+                            {"id": "r2", "last_modified": 9, "attachment": attachment("bb")},
+                            {"id": "r3", "last_modified": 8, "attachment": attachment("cc")},
+                            {"id": "r4", "last_modified": 7, "attachment": {"hash": "dd"}},
+                            {"id": "r5", "last_modified": 6},
+                            {"id": "r6", "last_modified": 5, "attachment": attachment("ee")}
+                        ],
+                        "timestamp": 10
+                    }),
+                ),
+            ]
+            .into_iter()
+            .map(|(url, body)| (url, body.to_string().into_bytes()))
+            .chain(
+                [("aa", 5), ("bb", 5), ("ee", 3)]
+                    .iter()
+                    .map(|(hash, size)| {
+                        (
+                            format!("{}/attachments/{}", fake_server, hash),
+                            hash[..1].repeat(*size).into_bytes(),
+                        )
+                    }),
+            )
+            .map(|(url, body)| TestResponse {
+                request_method: Method::GET,
+                request_url: url,
+                response_status: 200,
+                response_body: body,
+                response_headers: Headers::new(),
+            })
+            .collect()
+        };
+
+        let new_client = |max_attachments_size: Option<usize>| {
+            let mut builder = Client::builder()
+                .server_url(fake_server)
+                .http_client(Box::new(TestHttpClient::new(test_responses())))
+                .collection_name("prefetch")
+                .storage(Box::new(MemoryStorage::new()));
+            if let Some(max_size) = max_attachments_size {
+                builder = builder.max_attachments_size(max_size);
+            }
+            builder.build().unwrap()
+        };
+        let mut client = new_client(None);
+
+        let metadata: AttachmentMetadata = serde_json::from_value(attachment("aa")).unwrap();
+        client.sync(10).await.unwrap();
+        let _: Vec<u8> = client
+            .fetch_attachment_from_metadata(&metadata)
+            .await
+            .unwrap();
+
+        let report = client.download_all_attachments(2).await.unwrap();
+        assert!(!report.is_success());
+        assert_eq!(report.records.len(), 5);
+        assert_eq!(
+            *report.records["r1"].as_ref().unwrap(),
+            AttachmentStatus::AlreadyStored
+        );
+        assert_eq!(
+            *report.records["r2"].as_ref().unwrap(),
+            AttachmentStatus::Downloaded
+        );
+        assert!(matches!(
+            report.records["r3"],
+            Err(ClientError::APIError(KintoError::UnexpectedResponse { .. }))
+        ));
+        assert!(matches!(
+            report.records["r4"],
+            Err(ClientError::AttachmentMetadataError(_))
+        ));
+        assert!(matches!(
+            report.records["r6"],
+            Err(ClientError::IntegrityError(SignatureError::MismatchError(
+                _
+            )))
+        ));
+        assert_eq!(
+            client
+                .storage
+                .retrieve("attachment:main/prefetch:bb")
+                .unwrap(),
+            b"bbbbb"
+        );
+
+        let report = client.download_all_attachments(2).await.unwrap();
+        assert_eq!(
+            *report.records["r2"].as_ref().unwrap(),
+            AttachmentStatus::AlreadyStored
+        );
+
+        // Attachments evicted by the following ones are not reported as stored.
+        let mut client = new_client(Some(5));
+        client.sync(10).await.unwrap();
+        let report = client.download_all_attachments(1).await.unwrap();
+        let mut statuses: Vec<AttachmentStatus> = ["r1", "r2"]
+            .iter()
+            .map(|id| *report.records[*id].as_ref().unwrap())
+            .collect();
+        statuses.sort_by_key(|status| *status == AttachmentStatus::Downloaded);
+        assert_eq!(
+            statuses,
+            vec![AttachmentStatus::Evicted, AttachmentStatus::Downloaded]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_all_attachments_from_bundle() {
+        init();
+
+        let fake_server = "https://www.example.com/v1";
+        let attachment = |hash: &str| {
+            json!({
+                "hash": hash,
+                "size": 5,
+                "filename": "file.txt",
+                "location": hash,
+                "mimetype": "text/plain",
+            })
+        };
+        let bundle = make_bundle(&[
+            ("r1", b"aaaaa"),
+            ("r1.meta.json", b"{}"),
+            ("r2", b"bbbbb"),
+            ("r3", b"tampered"),
+        ]);
+        let test_responses: Vec<TestResponse> = vec![
+            (
+                fake_server.to_string(),
+                json!({
+                    "capabilities": {
+                        "attachments": {"base_url": format!("{}/attachments/", fake_server)}
+                    }
+                })
+                .to_string()
+                .into_bytes(),
+            ),
+            (
+                format!(
+                    "{}/buckets/main/collections/bundled/changeset?_expected=10",
+                    fake_server
+                ),
+                json!({
+                    "metadata": {"attachment": {"bundle": true}},
+                    "changes": [
+                        {"id": "r1", "last_modified": 10, "attachment": attachment("aa")},
+                        {"id": "r2", "last_modified": 9, "attachment": attachment("bb")},
+                        {"id": "r3", "last_modified": 8, "attachment": attachment("cc")},
+                        {"id": "r4", "last_modified": 7, "attachment": attachment("dd")}
+                    ],
+                    "timestamp": 10
+                })
+                .to_string()
+                .into_bytes(),
+            ),
+            (
+                format!("{}/attachments/bundles/main--bundled.zip", fake_server),
+                bundle,
+            ),
+            (format!("{}/attachments/cc", fake_server), b"ccccc".to_vec()),
+            (format!("{}/attachments/dd", fake_server), b"ddddd".to_vec()),
+        ]
+        .into_iter()
+        .map(|(url, body)| TestResponse {
+            request_method: Method::GET,
+            request_url: url,
+            response_status: 200,
+            response_body: body,
+            response_headers: Headers::new(),
+        })
+        .collect();
+
+        struct PrefixVerifier {}
+
+        #[async_trait]
+        impl Verification for PrefixVerifier {
+            async fn verify(
+                &self,
+                _: &'_ (dyn Requester + 'static),
+                _: &Collection,
+                _: &str,
+            ) -> Result<(), SignatureError> {
+                Ok(())
+            }
+
+            fn verify_nist384p_chain(
+                &self,
+                _: u64,
+                _: &[u8],
+                _: &str,
+                _: &str,
+                _: &[u8],
+                _: &[u8],
+            ) -> Result<(), SignatureError> {
+                unreachable!()
+            }
+
+            // Content is expected to start with the hash first byte.
+            fn verify_sha256_hash(
+                &self,
+                content: &[u8],
+                hash: &[u8],
+            ) -> Result<(), SignatureError> {
+                if content.len() == 5 && content[0] == format!("{:x}", hash[0] >> 4).as_bytes()[0] {
+                    Ok(())
+                } else {
+                    Err(SignatureError::MismatchError("bad content".to_string()))
+                }
+            }
+        }
+
+        let mut client = Client::builder()
+            .server_url(fake_server)
+            .http_client(Box::new(TestHttpClient::new(test_responses)))
+            .collection_name("bundled")
+            .storage(Box::new(MemoryStorage::new()))
+            .verifier(Box::new(PrefixVerifier {}))
+            .build()
+            .unwrap();
+        client.sync(10).await.unwrap();
+
+        let report = client.download_all_attachments(2).await.unwrap();
+        assert!(report.is_success());
+        assert_eq!(report.records.len(), 4);
+        for (hash, content) in &[
+            ("aa", "aaaaa"),
+            ("bb", "bbbbb"),
+            ("cc", "ccccc"),
+            ("dd", "ddddd"),
+        ] {
+            assert_eq!(
+                client
+                    .storage
+                    .retrieve(&format!("attachment:main/bundled:{}", hash))
+                    .unwrap(),
+                content.as_bytes()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_download_attachment() {
+        init();
+
+        struct DigestVerifier {}
+
+        impl Verification for DigestVerifier {
+            fn verify_nist384p_chain(
+                &self,
+                _: u64,
+                _: &[u8],
+                _: &str,
+                _: &str,
+                _: &[u8],
+                _: &[u8],
+            ) -> Result<(), SignatureError> {
+                unreachable!()
+            }
+
+            fn verify_sha256_hash(&self, _: &[u8], _: &[u8]) -> Result<(), SignatureError> {
+                unreachable!()
+            }
+        }
+
+        let fake_server = "https://www.example.com/v1";
+        let mut client = Client::builder()
+            .server_url(fake_server)
+            .http_client(Box::new(TestHttpClient::new(vec![
+                TestResponse {
+                    request_method: Method::GET,
+                    request_url: fake_server.to_string(),
+                    response_status: 200,
+                    response_body: json!({
+                        "capabilities": {
+                            "attachments": {"base_url": format!("{}/attachments/", fake_server)}
+                        }
+                    })
+                    .to_string()
+                    .into_bytes(),
+                    response_headers: Headers::new(),
+                },
+                TestResponse {
+                    request_method: Method::GET,
+                    request_url: format!("{}/attachments/file.txt", fake_server),
+                    response_status: 200,
+                    response_body: b"test\n".to_vec(),
+                    response_headers: Headers::new(),
+                },
+            ])))
+            .collection_name("models")
+            .verifier(Box::new(DigestVerifier {}))
+            .build()
+            .unwrap();
+
+        let metadata = AttachmentMetadata {
+            hash: "f2ca1bb6c7e907d06dafe4687e579fce76b37e4e93b7605022da52e6ccc26fd2".to_string(),
+            size: 5,
+            filename: "file.txt".to_string(),
+            location: "file.txt".to_string(),
+            mimetype: "text/plain".to_string(),
+        };
+
+        let mut content: Vec<u8> = Vec::new();
+        client
+            .download_attachment(&metadata, &mut content)
+            .await
+            .unwrap();
+        assert_eq!(content, b"test\n");
+
+        let folder = std::env::temp_dir().join("test_download_attachment");
+        let _ = std::fs::remove_dir_all(&folder);
+        std::fs::create_dir_all(&folder).unwrap();
+        let path = folder.join("file.txt");
+        client
+            .download_attachment_to_file(&metadata, &path)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"test\n");
+        std::fs::remove_file(&path).unwrap();
+
+        let too_small = AttachmentMetadata {
+            size: 4,
+            ..metadata
+        };
+        let err = client
+            .download_attachment_to_file(&too_small, &path)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "content signature could not be verified: signature mismatch: attachment is larger than 4 bytes"
+        );
+        // The partial file is removed.
+        assert_eq!(std::fs::read_dir(&folder).unwrap().count(), 0);
+        std::fs::remove_dir(&folder).unwrap();
+
+        let bad_hash = AttachmentMetadata {
+            size: 5,
+            hash: "00".repeat(32),
+            ..too_small
+        };
+        let err = client
+            .download_attachment(&bad_hash, &mut Vec::new())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "content signature could not be verified: signature mismatch: content did not match expected sha256 hash"
+        );
+    }
+
     #[tokio::test]
     async fn test_no_attachment() {
         init();
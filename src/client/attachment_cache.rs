@@ -0,0 +1,280 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use super::storage::{Storage, StorageError};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::io::{Cursor, Read};
+use zip::{
+    result::{ZipError, ZipResult},
+    ZipArchive,
+};
+
+/// Storage key of an attachment content.
+pub(crate) fn attachment_key(bid: &str, cid: &str, hash: &str) -> String {
+    format!("attachment:{}/{}:{}", bid, cid, hash)
+}
+
+/// An attachments bundle (`attachments.zip`).
+///
+/// A bundle has a `{id}` entry with the attachment content of each record, and
+/// a `{id}.meta.json` entry with the record itself, which is ignored.
+pub(crate) struct Bundle<'a> {
+    archive: ZipArchive<Cursor<&'a [u8]>>,
+}
+
+impl<'a> Bundle<'a> {
+    pub fn new(bytes: &'a [u8]) -> ZipResult<Self> {
+        Ok(Self {
+            archive: ZipArchive::new(Cursor::new(bytes))?,
+        })
+    }
+
+    /// Extract the attachment of a record, or `None` if the bundle does not have it.
+    ///
+    /// The sizes advertised by the archive are not trusted: no more than `size` bytes
+    /// are read, and entries of a different size are rejected.
+    pub fn read(&mut self, id: &str, size: usize) -> ZipResult<Option<Vec<u8>>> {
+        let file = match self.archive.by_name(id) {
+            Ok(file) if file.is_file() => file,
+            Ok(_) | Err(ZipError::FileNotFound) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        if file.size() != size as u64 {
+            return Err(ZipError::InvalidArchive("entry size differs from metadata"));
+        }
+        let mut content = Vec::new();
+        file.take(size as u64 + 1).read_to_end(&mut content)?;
+        if content.len() != size {
+            return Err(ZipError::InvalidArchive("entry size differs from metadata"));
+        }
+        Ok(Some(content))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    size: usize,
+    last_used: u64,
+}
+
+/// Index of the attachments stored locally for a collection.
+///
+/// It is used to remove attachments that are no longer referenced, and
+/// the least recently used ones when the cache exceeds its maximum size.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct AttachmentIndex {
+    entries: BTreeMap<String, Entry>,
+}
+
+impl AttachmentIndex {
+    fn key(bid: &str, cid: &str) -> String {
+        format!("attachments:{}/{}", bid, cid)
+    }
+
+    /// Return the index of the collection, or `None` if it was never saved.
+    pub fn load(storage: &dyn Storage, bid: &str, cid: &str) -> Option<Self> {
+        storage
+            .retrieve(&Self::key(bid, cid))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+    }
+
+    pub fn save(
+        &self,
+        storage: &mut dyn Storage,
+        bid: &str,
+        cid: &str,
+    ) -> Result<(), StorageError> {
+        let bytes = serde_json::to_vec(self).map_err(|err| {
+            StorageError::WriteError(format!("cannot serialize attachments index: {}", err))
+        })?;
+        storage.store(&Self::key(bid, cid), bytes)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the attachment is stored.
+    pub fn contains(&self, hash: &str) -> bool {
+        self.entries.contains_key(hash)
+    }
+
+    /// Mark the stored attachments as the most recently used, in order.
+    pub fn mark_used(&mut self, hashes: &[String]) {
+        for hash in hashes {
+            if let Some(size) = self.entries.get(hash).map(|entry| entry.size) {
+                self.touch(hash, size);
+            }
+        }
+    }
+
+    /// Mark the attachment as the most recently used.
+    pub fn touch(&mut self, hash: &str, size: usize) {
+        let last_used = self.entries.values().map(|e| e.last_used + 1).max();
+        self.entries.insert(
+            hash.to_string(),
+            Entry {
+                size,
+                last_used: last_used.unwrap_or(0),
+            },
+        );
+    }
+
+    /// Store an attachment and mark it as the most recently used.
+    ///
+    /// If `max_size` is specified, the least recently used attachments are then removed
+    /// from the storage until the total size fits.
+    pub fn store(
+        &mut self,
+        storage: &mut dyn Storage,
+        (bid, cid): (&str, &str),
+        hash: &str,
+        bytes: Vec<u8>,
+        max_size: Option<usize>,
+    ) -> Result<(), StorageError> {
+        let size = bytes.len();
+        storage.store(&attachment_key(bid, cid, hash), bytes)?;
+        self.touch(hash, size);
+        if let Some(max_size) = max_size {
+            for evicted in self.evict(max_size) {
+                storage.delete(&attachment_key(bid, cid, &evicted))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the least recently used attachments until the total size fits `max_size`,
+    /// and return their hashes. The most recently used is always kept.
+    pub fn evict(&mut self, max_size: usize) -> Vec<String> {
+        let mut by_usage: Vec<(&String, &Entry)> = self.entries.iter().collect();
+        by_usage.sort_by_key(|(_, entry)| entry.last_used);
+        let mut total: usize = by_usage.iter().map(|(_, entry)| entry.size).sum();
+        let mut evicted = Vec::new();
+        for (hash, entry) in by_usage.iter().take(by_usage.len().saturating_sub(1)) {
+            if total <= max_size {
+                break;
+            }
+            total -= entry.size;
+            evicted.push(hash.to_string());
+        }
+        self.remove(&evicted);
+        debug!("Evict {} attachments, size is now {}", evicted.len(), total);
+        evicted
+    }
+
+    /// Remove the attachments that are not in `referenced`, and return their hashes.
+    pub fn retain(&mut self, referenced: &HashSet<&str>) -> Vec<String> {
+        let unused: Vec<String> = self
+            .entries
+            .keys()
+            .filter(|hash| !referenced.contains(hash.as_str()))
+            .cloned()
+            .collect();
+        self.remove(&unused);
+        unused
+    }
+
+    /// Remove all attachments, and return their hashes.
+    pub fn clear(&mut self) -> Vec<String> {
+        let all = self.entries.keys().cloned().collect();
+        self.entries.clear();
+        all
+    }
+
+    fn remove(&mut self, hashes: &[String]) {
+        for hash in hashes {
+            self.entries.remove(hash);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{AttachmentIndex, Bundle};
+    use crate::client::storage::memory_storage::MemoryStorage;
+    use std::collections::HashSet;
+    use std::io::{Cursor, Write};
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    /// Return a bundle with the specified entries.
+    pub fn make_bundle(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in entries {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(content).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_evict_least_recently_used() {
+        let mut index = AttachmentIndex::default();
+        index.touch("a", 10);
+        index.touch("b", 10);
+        index.touch("c", 10);
+        index.touch("a", 10);
+
+        assert!(index.evict(30).is_empty());
+        assert_eq!(index.evict(15), vec!["b", "c"]);
+        // The most recently used is kept, even if too big.
+        assert!(index.evict(5).is_empty());
+    }
+
+    #[test]
+    fn test_mark_used() {
+        let mut index = AttachmentIndex::default();
+        index.touch("a", 10);
+        index.touch("b", 10);
+        index.mark_used(&["a".to_string(), "unknown".to_string()]);
+
+        assert!(index.contains("a"));
+        assert!(!index.contains("unknown"));
+        assert_eq!(index.evict(10), vec!["b"]);
+    }
+
+    #[test]
+    fn test_retain_referenced() {
+        let mut index = AttachmentIndex::default();
+        index.touch("a", 10);
+        index.touch("b", 10);
+
+        let referenced: HashSet<&str> = vec!["b", "z"].into_iter().collect();
+        assert_eq!(index.retain(&referenced), vec!["a"]);
+        assert_eq!(index.clear(), vec!["b"]);
+        assert_eq!(index.len(), 0);
+    }
+
+    #[test]
+    fn test_load_and_save() {
+        let mut storage = MemoryStorage::new();
+        assert!(AttachmentIndex::load(&storage, "main", "cid").is_none());
+
+        let mut index = AttachmentIndex::default();
+        index.touch("a", 10);
+        index.save(&mut storage, "main", "cid").unwrap();
+
+        let mut index = AttachmentIndex::load(&storage, "main", "cid").unwrap();
+        assert_eq!(index.clear(), vec!["a"]);
+    }
+
+    #[test]
+    fn test_read_bundle() {
+        let bundle = make_bundle(&[("a", b"aaa"), ("a.meta.json", b"{}"), ("b", b"")]);
+        let mut bundle = Bundle::new(&bundle).unwrap();
+        assert_eq!(bundle.read("a", 3).unwrap().unwrap(), b"aaa");
+        assert!(bundle.read("b", 0).unwrap().unwrap().is_empty());
+        assert!(bundle.read("c", 3).unwrap().is_none());
+
+        // Entries whose size differs from the metadata are rejected.
+        assert!(bundle.read("a", 2).is_err());
+        assert!(bundle.read("a", 4).is_err());
+
+        assert!(Bundle::new(b"not a zip").is_err());
+    }
+}
@@ -0,0 +1,85 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use super::signatures::epoch_seconds;
+use super::{ClientError, Storage, BACKOFF_STORAGE_KEY};
+
+/// Storage key of the backoff deadline of a server.
+fn backoff_key(server_url: &str) -> String {
+    format!("{}:{}", BACKOFF_STORAGE_KEY, server_url)
+}
+
+/// Fail if the server asked clients to back off, in this instance or in another one
+/// sharing the same storage.
+///
+/// Once expired, the deadline is forgotten and removed from the storage.
+pub(crate) fn check_backoff(
+    backoff_until: &mut Option<u64>,
+    storage: &mut dyn Storage,
+    server_url: &str,
+) -> Result<(), ClientError> {
+    let key = backoff_key(server_url);
+    let stored_until = storage
+        .retrieve(&key)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .and_then(|s| s.parse::<u64>().ok());
+    if let Some(until) = (*backoff_until).max(stored_until) {
+        let now = epoch_seconds();
+        if now < until {
+            return Err(ClientError::BackoffError(until - now));
+        }
+        *backoff_until = None;
+        if stored_until.is_some() {
+            storage.delete(&key)?;
+        }
+    }
+    Ok(())
+}
+
+/// Keep in state, and persist, that the server asked clients to back off for a while.
+pub(crate) fn set_backoff(
+    backoff_until: &mut Option<u64>,
+    storage: &mut dyn Storage,
+    server_url: &str,
+    backoff_secs: Option<u64>,
+) -> Result<(), ClientError> {
+    if let Some(secs) = backoff_secs {
+        let until = epoch_seconds() + secs;
+        *backoff_until = Some(until);
+        storage.store(&backoff_key(server_url), until.to_string().into_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{backoff_key, check_backoff, set_backoff};
+    use crate::client::{ClientError, MemoryStorage, Storage, StorageError};
+
+    #[test]
+    fn test_backoff_is_namespaced_by_server() {
+        let mut storage = MemoryStorage::new();
+        set_backoff(&mut None, &mut storage, "https://a/v1", Some(300)).unwrap();
+
+        assert!(matches!(
+            check_backoff(&mut None, &mut storage, "https://a/v1"),
+            Err(ClientError::BackoffError(_))
+        ));
+        assert!(check_backoff(&mut None, &mut storage, "https://b/v1").is_ok());
+    }
+
+    #[test]
+    fn test_expired_backoff_is_deleted() {
+        let mut storage = MemoryStorage::new();
+        let key = backoff_key("https://a/v1");
+        storage.store(&key, b"0".to_vec()).unwrap();
+
+        check_backoff(&mut None, &mut storage, "https://a/v1").unwrap();
+        assert!(matches!(
+            storage.retrieve(&key),
+            Err(StorageError::KeyNotFound { .. })
+        ));
+    }
+}
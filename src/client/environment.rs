@@ -0,0 +1,59 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use super::{DEFAULT_SERVER_URL, PROD_CERT_ROOT_HASH};
+
+pub const STAGE_SERVER_URL: &str = "https://remote-settings.allizom.org/v1";
+pub const DEV_SERVER_URL: &str = "https://remote-settings-dev.allizom.org/v1";
+/// Root certificate hash of the stage and dev environments.
+pub const STAGE_CERT_ROOT_HASH: &str = "45:C3:7F:3A:09:A6:D7:0E:0F:A3:21:FB:29:75:3B:A7:99:8F:12:59:B3:27:72:76:8F:23:CC:DC:24:83:67:98";
+
+/// A Remote Settings server, along with the root of its certificate chains.
+///
+/// See [`ClientBuilder::environment`](crate::client::ClientBuilder::environment).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Environment {
+    /// The production server (*default*).
+    #[default]
+    Prod,
+    /// The stage server.
+    Stage,
+    /// The dev server.
+    Dev,
+    /// Another server.
+    ///
+    /// Collections are expected to be signed by [`DEFAULT_SIGNER_NAME`](crate::DEFAULT_SIGNER_NAME),
+    /// unless a signer is specified by the server capabilities.
+    Custom {
+        server_url: String,
+        cert_root_hash: String,
+    },
+}
+
+impl Environment {
+    /// Return the URL of the server.
+    pub fn server_url(&self) -> &str {
+        match self {
+            Environment::Prod => DEFAULT_SERVER_URL,
+            Environment::Stage => STAGE_SERVER_URL,
+            Environment::Dev => DEV_SERVER_URL,
+            Environment::Custom { server_url, .. } => server_url,
+        }
+    }
+
+    /// Return the SHA256 fingerprint of the root certificate.
+    pub fn cert_root_hash(&self) -> &str {
+        match self {
+            Environment::Prod => PROD_CERT_ROOT_HASH,
+            Environment::Stage | Environment::Dev => STAGE_CERT_ROOT_HASH,
+            Environment::Custom { cert_root_hash, .. } => cert_root_hash,
+        }
+    }
+
+    /// Whether the collections are signed like on the Mozilla servers (eg. with
+    /// `onecrl.content-signature.mozilla.org` for the `security-state` bucket).
+    pub(crate) fn uses_mozilla_signers(&self) -> bool {
+        !matches!(self, Environment::Custom { .. })
+    }
+}
@@ -0,0 +1,69 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+/// A JSON dump of a collection, used to seed an empty local storage.
+///
+/// The dump is an object with the `metadata` (including the signature), `records`
+/// (or `data`) and `timestamp` of the collection, and optionally its `bid` and `cid`.
+///
+/// See [`ClientBuilder::initial_data`](crate::client::ClientBuilder::initial_data).
+#[derive(Clone)]
+pub enum InitialData {
+    /// Content of the dump.
+    Bytes(Cow<'static, [u8]>),
+    /// Path of the dump file, read only when the local storage is empty.
+    File(PathBuf),
+}
+
+impl InitialData {
+    pub(crate) fn read(&self) -> std::io::Result<Cow<'_, [u8]>> {
+        match self {
+            InitialData::Bytes(bytes) => Ok(Cow::Borrowed(bytes)),
+            InitialData::File(path) => Ok(Cow::Owned(std::fs::read(path)?)),
+        }
+    }
+}
+
+impl std::fmt::Debug for InitialData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InitialData::Bytes(bytes) => write!(f, "Bytes({} bytes)", bytes.len()),
+            InitialData::File(path) => write!(f, "File({:?})", path),
+        }
+    }
+}
+
+impl From<Vec<u8>> for InitialData {
+    fn from(bytes: Vec<u8>) -> Self {
+        InitialData::Bytes(Cow::Owned(bytes))
+    }
+}
+
+impl From<&'static [u8]> for InitialData {
+    fn from(bytes: &'static [u8]) -> Self {
+        InitialData::Bytes(Cow::Borrowed(bytes))
+    }
+}
+
+// For `include_bytes!()`.
+impl<const N: usize> From<&'static [u8; N]> for InitialData {
+    fn from(bytes: &'static [u8; N]) -> Self {
+        InitialData::Bytes(Cow::Borrowed(bytes))
+    }
+}
+
+impl From<PathBuf> for InitialData {
+    fn from(path: PathBuf) -> Self {
+        InitialData::File(path)
+    }
+}
+
+impl From<&Path> for InitialData {
+    fn from(path: &Path) -> Self {
+        InitialData::File(path.to_path_buf())
+    }
+}
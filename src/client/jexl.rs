@@ -0,0 +1,760 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Evaluation of [JEXL](https://github.com/mozilla/mozjexl) filter expressions.
+//!
+//! Records may have a `filter_expression` field, which is evaluated against a context
+//! provided by the application (eg. `env.locale`, `env.version`...) in order to decide
+//! whether the record applies to this client.
+//!
+//! The supported syntax is the subset used in Remote Settings filters:
+//! * literals (numbers, strings, `true`, `false`, `null`, arrays and objects);
+//! * identifiers and property access (`env.appinfo.OS`, `env["locale"]`, `list[0]`);
+//! * operators `!`, `+`, `-`, `*`, `/`, `//`, `%`, `^`, `==`, `!=`, `<`, `<=`, `>`, `>=`,
+//!   `in`, `&&`, `||` and the ternary `? :`;
+//! * transforms `length`, `keys` and `versionCompare` (eg. `env.version|versionCompare("100") >= 0`).
+//!
+//! Expressions using other operators or transforms, or nested more than [`MAX_DEPTH`]
+//! levels deep, cannot be evaluated.
+
+use serde_json::{Map, Number, Value};
+use std::cmp::Ordering;
+use thiserror::Error;
+
+/// Maximum nesting of expressions, so that evaluating them cannot overflow the stack.
+pub const MAX_DEPTH: usize = 100;
+
+#[derive(Debug, PartialEq, Error)]
+pub enum FilterError {
+    #[error("cannot parse expression: {0}")]
+    ParseError(String),
+    #[error("cannot evaluate expression: {0}")]
+    EvaluationError(String),
+}
+
+/// Evaluate the expression against the specified context.
+///
+/// # Examples
+/// ```rust
+/// # use remote_settings_client::client::jexl::evaluate;
+/// # use serde_json::json;
+/// let context = json!({"env": {"locale": "fr-FR", "version": "115.0.2"}});
+/// let value = evaluate(
+///     "env.locale in ['fr-FR', 'fr-CA'] && env.version|versionCompare('100') >= 0",
+///     &context,
+/// )
+/// .unwrap();
+/// assert_eq!(value, json!(true));
+/// ```
+///
+/// # Errors
+/// If the expression is invalid, a [`FilterError::ParseError`] is returned.
+///
+/// If the expression cannot be evaluated (eg. unknown transform), a [`FilterError::EvaluationError`] is returned.
+pub fn evaluate(expression: &str, context: &Value) -> Result<Value, FilterError> {
+    let tokens = tokenize(expression)?;
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        depth: 0,
+    };
+    let ast = parser.parse_expression()?;
+    if parser.pos < parser.tokens.len() {
+        return Err(FilterError::ParseError(format!(
+            "unexpected {:?}",
+            parser.tokens[parser.pos]
+        )));
+    }
+    eval(&ast, context)
+}
+
+/// Return whether the value is considered true, like in Javascript.
+pub fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => matches!(n.as_f64(), Some(f) if f != 0.0 && !f.is_nan()),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(_) | Value::Object(_) => true,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    String(String),
+    Identifier(String),
+    Operator(&'static str),
+}
+
+const OPERATORS: [&str; 27] = [
+    "==", "!=", "<=", ">=", "&&", "||", "//", "<", ">", "!", "+", "-", "*", "/", "%", "^", "?",
+    ":", "|", ".", ",", "(", ")", "[", "]", "{", "}",
+];
+
+fn tokenize(input: &str) -> Result<Vec<Token>, FilterError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let literal: String = chars[start..i].iter().collect();
+            let number = literal
+                .parse()
+                .map_err(|_| FilterError::ParseError(format!("invalid number {}", literal)))?;
+            tokens.push(Token::Number(number));
+        } else if c == '"' || c == '\'' {
+            let mut s = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(FilterError::ParseError("unterminated string".into())),
+                    Some(&q) if q == c => break,
+                    Some('\\') => {
+                        i += 1;
+                        match chars.get(i) {
+                            Some(&e) => s.push(e),
+                            None => {
+                                return Err(FilterError::ParseError("unterminated string".into()))
+                            }
+                        }
+                    }
+                    Some(&other) => s.push(other),
+                }
+                i += 1;
+            }
+            i += 1;
+            tokens.push(Token::String(s));
+        } else if c.is_alphabetic() || c == '_' || c == '$' {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$')
+            {
+                i += 1;
+            }
+            tokens.push(Token::Identifier(chars[start..i].iter().collect()));
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            match OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+                Some(op) => {
+                    tokens.push(Token::Operator(op));
+                    i += op.len();
+                }
+                None => {
+                    return Err(FilterError::ParseError(format!(
+                        "unexpected character {:?}",
+                        c
+                    )))
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug)]
+enum Expression {
+    Literal(Value),
+    Array(Vec<Expression>),
+    Object(Vec<(String, Expression)>),
+    Identifier(String),
+    Member(Box<Expression>, Box<Expression>),
+    Transform(Box<Expression>, String, Vec<Expression>),
+    Unary(&'static str, Box<Expression>),
+    Binary(&'static str, Box<Expression>, Box<Expression>),
+    Conditional(Box<Expression>, Box<Expression>, Box<Expression>),
+}
+
+/// Precedence of binary operators, as defined by JEXL.
+fn precedence(op: &str) -> Option<u8> {
+    match op {
+        "||" | "&&" => Some(10),
+        "==" | "!=" | "<" | "<=" | ">" | ">=" | "in" => Some(20),
+        "+" | "-" => Some(30),
+        "*" | "/" | "//" | "%" => Some(40),
+        "^" => Some(50),
+        _ => None,
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    // Nesting of the expression being parsed.
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token, FilterError> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| FilterError::ParseError("unexpected end of expression".into()))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Operator(o)) if *o == op) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), FilterError> {
+        if self.eat(op) {
+            Ok(())
+        } else {
+            Err(FilterError::ParseError(format!(
+                "expected {:?}, found {:?}",
+                op,
+                self.peek()
+            )))
+        }
+    }
+
+    /// Enter a nested expression, and fail if the maximum depth is exceeded.
+    fn nest(&mut self) -> Result<(), FilterError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(FilterError::ParseError(format!(
+                "expression is nested more than {} levels deep",
+                MAX_DEPTH
+            )));
+        }
+        Ok(())
+    }
+
+    fn parse_expression(&mut self) -> Result<Expression, FilterError> {
+        let condition = self.parse_binary(0)?;
+        if self.eat("?") {
+            let consequent = self.parse_expression()?;
+            self.expect(":")?;
+            let alternate = self.parse_expression()?;
+            return Ok(Expression::Conditional(
+                Box::new(condition),
+                Box::new(consequent),
+                Box::new(alternate),
+            ));
+        }
+        Ok(condition)
+    }
+
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expression, FilterError> {
+        let depth = self.depth;
+        let mut left = self.parse_unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Operator(op)) => *op,
+                Some(Token::Identifier(id)) if id == "in" => "in",
+                _ => break,
+            };
+            let prec = match precedence(op) {
+                Some(prec) if prec > min_precedence => prec,
+                _ => break,
+            };
+            self.pos += 1;
+            // Each operand nests the previous ones.
+            self.nest()?;
+            let right = self.parse_binary(prec)?;
+            left = Expression::Binary(op, Box::new(left), Box::new(right));
+        }
+        self.depth = depth;
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression, FilterError> {
+        self.nest()?;
+        let expr = if self.eat("!") {
+            Expression::Unary("!", Box::new(self.parse_unary()?))
+        } else if self.eat("-") {
+            Expression::Unary("-", Box::new(self.parse_unary()?))
+        } else {
+            self.parse_postfix()?
+        };
+        self.depth -= 1;
+        Ok(expr)
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expression, FilterError> {
+        let depth = self.depth;
+        let mut expr = self.parse_primary()?;
+        loop {
+            if self.eat(".") {
+                match self.next()? {
+                    Token::Identifier(name) => {
+                        expr = Expression::Member(
+                            Box::new(expr),
+                            Box::new(Expression::Literal(Value::String(name))),
+                        )
+                    }
+                    other => {
+                        return Err(FilterError::ParseError(format!(
+                            "expected property name, found {:?}",
+                            other
+                        )))
+                    }
+                }
+            } else if self.eat("[") {
+                let property = self.parse_expression()?;
+                self.expect("]")?;
+                expr = Expression::Member(Box::new(expr), Box::new(property));
+            } else if self.eat("|") {
+                let name = match self.next()? {
+                    Token::Identifier(name) => name,
+                    other => {
+                        return Err(FilterError::ParseError(format!(
+                            "expected transform name, found {:?}",
+                            other
+                        )))
+                    }
+                };
+                let mut args = Vec::new();
+                if self.eat("(") && !self.eat(")") {
+                    loop {
+                        args.push(self.parse_expression()?);
+                        if self.eat(")") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                expr = Expression::Transform(Box::new(expr), name, args);
+            } else {
+                self.depth = depth;
+                return Ok(expr);
+            }
+            // Each property access or transform nests the previous ones.
+            self.nest()?;
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, FilterError> {
+        match self.next()? {
+            Token::Number(n) => Ok(Expression::Literal(number(n)?)),
+            Token::String(s) => Ok(Expression::Literal(Value::String(s))),
+            Token::Identifier(id) => Ok(match id.as_str() {
+                "true" => Expression::Literal(Value::Bool(true)),
+                "false" => Expression::Literal(Value::Bool(false)),
+                "null" => Expression::Literal(Value::Null),
+                _ => Expression::Identifier(id),
+            }),
+            Token::Operator("(") => {
+                let expr = self.parse_expression()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Token::Operator("[") => {
+                let mut items = Vec::new();
+                if !self.eat("]") {
+                    loop {
+                        items.push(self.parse_expression()?);
+                        if self.eat("]") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                Ok(Expression::Array(items))
+            }
+            Token::Operator("{") => {
+                let mut entries = Vec::new();
+                if !self.eat("}") {
+                    loop {
+                        let key = match self.next()? {
+                            Token::Identifier(key) | Token::String(key) => key,
+                            other => {
+                                return Err(FilterError::ParseError(format!(
+                                    "expected object key, found {:?}",
+                                    other
+                                )))
+                            }
+                        };
+                        self.expect(":")?;
+                        entries.push((key, self.parse_expression()?));
+                        if self.eat("}") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                Ok(Expression::Object(entries))
+            }
+            other => Err(FilterError::ParseError(format!("unexpected {:?}", other))),
+        }
+    }
+}
+
+fn number(n: f64) -> Result<Value, FilterError> {
+    if n.fract() == 0.0 && n.abs() < i64::MAX as f64 {
+        return Ok(Value::from(n as i64));
+    }
+    Number::from_f64(n)
+        .map(Value::Number)
+        .ok_or_else(|| FilterError::EvaluationError(format!("invalid number {}", n)))
+}
+
+fn as_number(value: &Value, op: &str) -> Result<f64, FilterError> {
+    value.as_f64().ok_or_else(|| {
+        FilterError::EvaluationError(format!("operator {} expects numbers, got {}", op, value))
+    })
+}
+
+fn equals(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        _ => left == right,
+    }
+}
+
+fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        // Like Javascript, compare UTF-16 code units.
+        (Value::String(a), Value::String(b)) => Some(a.encode_utf16().cmp(b.encode_utf16())),
+        _ => None,
+    }
+}
+
+fn eval(expr: &Expression, context: &Value) -> Result<Value, FilterError> {
+    Ok(match expr {
+        Expression::Literal(value) => value.clone(),
+        Expression::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|item| eval(item, context))
+                .collect::<Result<_, _>>()?,
+        ),
+        Expression::Object(entries) => {
+            let mut map = Map::new();
+            for (key, value) in entries {
+                map.insert(key.clone(), eval(value, context)?);
+            }
+            Value::Object(map)
+        }
+        Expression::Identifier(name) => context.get(name).cloned().unwrap_or(Value::Null),
+        Expression::Member(object, property) => {
+            let object = eval(object, context)?;
+            match (&object, eval(property, context)?) {
+                (Value::Object(map), Value::String(key)) => {
+                    map.get(&key).cloned().unwrap_or(Value::Null)
+                }
+                (Value::Array(items), Value::Number(index)) => index
+                    .as_u64()
+                    .and_then(|i| items.get(i as usize))
+                    .cloned()
+                    .unwrap_or(Value::Null),
+                _ => Value::Null,
+            }
+        }
+        Expression::Transform(subject, name, args) => {
+            let subject = eval(subject, context)?;
+            let args = args
+                .iter()
+                .map(|arg| eval(arg, context))
+                .collect::<Result<Vec<_>, _>>()?;
+            transform(name, &subject, &args)?
+        }
+        Expression::Unary(op, operand) => {
+            let value = eval(operand, context)?;
+            match *op {
+                "!" => Value::Bool(!is_truthy(&value)),
+                _ => number(-as_number(&value, op)?)?,
+            }
+        }
+        Expression::Conditional(condition, consequent, alternate) => {
+            if is_truthy(&eval(condition, context)?) {
+                eval(consequent, context)?
+            } else {
+                eval(alternate, context)?
+            }
+        }
+        Expression::Binary(op, left, right) => {
+            let left = eval(left, context)?;
+            // Logical operators return one of their operands, and short-circuit.
+            match *op {
+                "&&" if !is_truthy(&left) => return Ok(left),
+                "||" if is_truthy(&left) => return Ok(left),
+                "&&" | "||" => return eval(right, context),
+                _ => (),
+            }
+            let right = eval(right, context)?;
+            match *op {
+                "==" => Value::Bool(equals(&left, &right)),
+                "!=" => Value::Bool(!equals(&left, &right)),
+                "<" => Value::Bool(compare(&left, &right) == Some(Ordering::Less)),
+                "<=" => Value::Bool(matches!(
+                    compare(&left, &right),
+                    Some(Ordering::Less | Ordering::Equal)
+                )),
+                ">" => Value::Bool(compare(&left, &right) == Some(Ordering::Greater)),
+                ">=" => Value::Bool(matches!(
+                    compare(&left, &right),
+                    Some(Ordering::Greater | Ordering::Equal)
+                )),
+                "in" => Value::Bool(match (&left, &right) {
+                    (_, Value::Array(items)) => items.iter().any(|item| equals(item, &left)),
+                    (Value::String(needle), Value::String(haystack)) => {
+                        haystack.contains(needle.as_str())
+                    }
+                    _ => false,
+                }),
+                "+" => match (&left, &right) {
+                    (Value::String(a), b) => Value::String(format!("{}{}", a, to_string(b))),
+                    (a, Value::String(b)) => Value::String(format!("{}{}", to_string(a), b)),
+                    _ => number(as_number(&left, op)? + as_number(&right, op)?)?,
+                },
+                _ => {
+                    let (a, b) = (as_number(&left, op)?, as_number(&right, op)?);
+                    number(match *op {
+                        "-" => a - b,
+                        "*" => a * b,
+                        "/" => a / b,
+                        "//" => (a / b).floor(),
+                        "^" => a.powf(b),
+                        "%" => a % b,
+                        _ => {
+                            return Err(FilterError::EvaluationError(format!(
+                                "unknown operator {}",
+                                op
+                            )))
+                        }
+                    })?
+                }
+            }
+        }
+    })
+}
+
+fn to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn transform(name: &str, subject: &Value, args: &[Value]) -> Result<Value, FilterError> {
+    match (name, subject) {
+        ("length", Value::String(s)) => Ok(Value::from(s.chars().count())),
+        ("length", Value::Array(items)) => Ok(Value::from(items.len())),
+        ("length", Value::Object(map)) => Ok(Value::from(map.len())),
+        ("keys", Value::Object(map)) => Ok(Value::Array(
+            map.keys().map(|k| Value::String(k.clone())).collect(),
+        )),
+        ("versionCompare", Value::String(a)) => match args.first() {
+            Some(Value::String(b)) => Ok(Value::from(match version_compare(a, b) {
+                Ordering::Less => -1,
+                Ordering::Equal => 0,
+                Ordering::Greater => 1,
+            })),
+            _ => Err(FilterError::EvaluationError(
+                "versionCompare expects a version string".into(),
+            )),
+        },
+        ("length", _) | ("keys", _) | ("versionCompare", _) => Err(FilterError::EvaluationError(
+            format!("transform {} cannot be applied to {}", name, subject),
+        )),
+        _ => Err(FilterError::EvaluationError(format!(
+            "unknown transform {}",
+            name
+        ))),
+    }
+}
+
+/// Compare two versions like Firefox does (eg. `"100.0a1" < "100.0b2" < "100.0" < "100.0.1"`).
+///
+/// Each dot-separated part is made of a number, a string, a number and a string.
+/// A missing string is greater than any string, and missing numbers are 0.
+fn version_compare(a: &str, b: &str) -> Ordering {
+    let (mut a_parts, mut b_parts) = (a.split('.'), b.split('.'));
+    loop {
+        match (a_parts.next(), b_parts.next()) {
+            (None, None) => return Ordering::Equal,
+            (a_part, b_part) => {
+                let ordering =
+                    version_part(a_part.unwrap_or("0")).cmp(&version_part(b_part.unwrap_or("0")));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+        }
+    }
+}
+
+type VersionPart<'a> = (u64, VersionString<'a>, u64, VersionString<'a>);
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum VersionString<'a> {
+    // Declared first, so that any string is lower than no string.
+    Some(&'a str),
+    None,
+}
+
+fn version_part(part: &str) -> VersionPart<'_> {
+    fn split_number(s: &str) -> (u64, &str) {
+        let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        (s[..end].parse().unwrap_or(0), &s[end..])
+    }
+    fn split_string(s: &str) -> (VersionString<'_>, &str) {
+        let end = s.find(|c: char| c.is_ascii_digit()).unwrap_or(s.len());
+        match &s[..end] {
+            "" => (VersionString::None, &s[end..]),
+            string => (VersionString::Some(string), &s[end..]),
+        }
+    }
+    let (a, rest) = split_number(part);
+    let (b, rest) = split_string(rest);
+    let (c, rest) = split_number(rest);
+    let d = match rest {
+        "" => VersionString::None,
+        rest => VersionString::Some(rest),
+    };
+    (a, b, c, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{evaluate, version_compare, FilterError, MAX_DEPTH};
+    use serde_json::{json, Value};
+    use std::cmp::Ordering;
+
+    fn eval(expression: &str) -> Value {
+        let context = json!({
+            "env": {
+                "locale": "fr-FR",
+                "version": "115.0.2",
+                "appinfo": {"OS": "Darwin"},
+                "channels": ["beta", "release"]
+            },
+            "count": 3
+        });
+        evaluate(expression, &context).unwrap()
+    }
+
+    #[test]
+    fn test_literals_and_operators() {
+        assert_eq!(eval("1 + 2 * 3"), json!(7));
+        assert_eq!(eval("(1 + 2) * 3"), json!(9));
+        assert_eq!(eval("7 // 2 + 7 % 2"), json!(4));
+        assert_eq!(eval("2 ^ 3 * 2 + 1"), json!(17));
+        assert_eq!(eval("-count + 0.5"), json!(-2.5));
+        assert_eq!(eval("'a' + \"b\" + 1"), json!("ab1"));
+        assert_eq!(eval("[1, 'two', null]"), json!([1, "two", null]));
+        assert_eq!(eval("{a: 1, 'b': [true]}.b[0]"), json!(true));
+        assert_eq!(eval("1 == 1.0 && 'a' != 'b'"), json!(true));
+        assert_eq!(eval("2 < 10 && 'abc' < 'b' && !(2 >= 3)"), json!(true));
+        assert_eq!(eval("count > 1 ? 'many' : 'few'"), json!("many"));
+        assert_eq!(eval("null || 0 || 'default'"), json!("default"));
+        assert_eq!(eval("false && unknown.field"), json!(false));
+    }
+
+    #[test]
+    fn test_string_comparison() {
+        // Strings are compared by UTF-16 code units, so an astral character (surrogate
+        // pair starting with 0xD83D) is lower than a BMP character like U+FF61.
+        assert_eq!(eval("'\u{1F600}' < '\u{FF61}'"), json!(true));
+        assert_eq!(eval("'\u{FF61}' > '\u{1F600}'"), json!(true));
+        assert_eq!(eval("'a' <= 'a' && 'B' < 'a'"), json!(true));
+    }
+
+    #[test]
+    fn test_context_access() {
+        assert_eq!(eval("env.appinfo.OS == 'Darwin'"), json!(true));
+        assert_eq!(eval("env['locale']"), json!("fr-FR"));
+        assert_eq!(eval("env.channels[1]"), json!("release"));
+        assert_eq!(eval("env.unknown.field"), json!(null));
+        assert_eq!(eval("'beta' in env.channels"), json!(true));
+        assert_eq!(eval("'FR' in env.locale"), json!(true));
+        assert_eq!(eval("env.locale in ['en-US', 'en-GB']"), json!(false));
+    }
+
+    #[test]
+    fn test_transforms() {
+        assert_eq!(eval("env.channels|length"), json!(2));
+        assert_eq!(eval("env.appinfo|keys"), json!(["OS"]));
+        assert_eq!(
+            eval("env.version|versionCompare('115.0a1') > 0"),
+            json!(true)
+        );
+        assert_eq!(eval("env.version|versionCompare('116') < 0"), json!(true));
+    }
+
+    #[test]
+    fn test_errors() {
+        let context = json!({});
+        assert!(matches!(
+            evaluate("1 +", &context),
+            Err(FilterError::ParseError(_))
+        ));
+        assert!(matches!(
+            evaluate("'abc", &context),
+            Err(FilterError::ParseError(_))
+        ));
+        assert!(matches!(
+            evaluate("a b", &context),
+            Err(FilterError::ParseError(_))
+        ));
+        assert_eq!(
+            evaluate("1|unknown", &context),
+            Err(FilterError::EvaluationError(
+                "unknown transform unknown".to_string()
+            ))
+        );
+        assert!(matches!(
+            evaluate("[] * 2", &context),
+            Err(FilterError::EvaluationError(_))
+        ));
+        assert!(matches!(
+            evaluate("1 =~ 1", &context),
+            Err(FilterError::ParseError(_))
+        ));
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let context = json!({"a": 1});
+        let nested = |depth: usize, open: &str, close: &str| {
+            format!("{}1{}", open.repeat(depth), close.repeat(depth))
+        };
+        assert_eq!(evaluate(&nested(10, "(", ")"), &context), Ok(json!(1)));
+
+        let too_deep = [
+            nested(MAX_DEPTH, "(", ")"),
+            nested(MAX_DEPTH, "[", "]"),
+            nested(100_000, "(", ")"),
+            "!".repeat(100_000) + "1",
+            vec!["1"; 100_000].join(" + "),
+            "a".to_string() + &".b".repeat(100_000),
+        ];
+        for expression in &too_deep {
+            assert!(matches!(
+                evaluate(expression, &context),
+                Err(FilterError::ParseError(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_version_compare() {
+        assert_eq!(version_compare("1.0", "1.0.0"), Ordering::Equal);
+        assert_eq!(version_compare("1.0a1", "1.0b1"), Ordering::Less);
+        assert_eq!(version_compare("1.0b2", "1.0"), Ordering::Less);
+        assert_eq!(version_compare("1.0", "1.0.1"), Ordering::Less);
+        assert_eq!(version_compare("10.0", "9.9"), Ordering::Greater);
+        assert_eq!(version_compare("1.0a2", "1.0a10"), Ordering::Less);
+    }
+}
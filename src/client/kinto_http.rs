@@ -2,8 +2,11 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use crate::client::{net::Headers, net::Method, net::Requester, net::Response};
+use crate::client::{
+    net::parse_retry_after, net::Headers, net::Method, net::Requester, net::Response,
+};
 use std::collections::HashMap;
+use std::time::UNIX_EPOCH;
 
 use log::{debug, info};
 use serde::{Deserialize, Serialize};
@@ -40,17 +43,20 @@ pub enum KintoError {
     ServerError {
         method: Method,
         url: String,
-        response: Response,
-        info: ErrorResponse,
+        response: Box<Response>,
+        info: Box<ErrorResponse>,
         retry_after: Option<u64>,
     },
     #[error("the server responded with unexpected content on GET {}: HTTP {}", url, response.status)]
-    UnexpectedResponse { url: String, response: Response },
+    UnexpectedResponse {
+        url: String,
+        response: Box<Response>,
+    },
     #[error("invalid request on GET {}: {}", url, info)]
     ClientRequestError {
         url: String,
-        response: Response,
-        info: ErrorResponse,
+        response: Box<Response>,
+        info: Box<ErrorResponse>,
     },
     #[error("changeset timestamp could not be parsed: {0}")]
     InvalidChangesetTimestamp(String),
@@ -66,6 +72,18 @@ pub enum KintoError {
 
 type Result<T> = std::result::Result<T, KintoError>;
 
+impl KintoError {
+    /// Return the server response that caused this error, if any.
+    pub fn response(&self) -> Option<&Response> {
+        match self {
+            KintoError::ServerError { response, .. }
+            | KintoError::UnexpectedResponse { response, .. }
+            | KintoError::ClientRequestError { response, .. } => Some(response),
+            _ => None,
+        }
+    }
+}
+
 impl std::fmt::Display for ErrorResponse {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -76,17 +94,9 @@ impl std::fmt::Display for ErrorResponse {
     }
 }
 
-pub async fn get_latest_change_timestamp(
-    requester: &'_ (dyn Requester + 'static),
-    server: &str,
-    bid: &str,
-    cid: &str,
-) -> Result<u64> {
-    // When we fetch the monitor/changes endpoint manually (ie. not from a push notification)
-    // we cannot know the current timestamp, and use 0 arbitrarily.
-    let expected = 0;
-    let response = get_changeset(requester, server, "monitor", "changes", expected, None).await?;
-    let change = response
+/// Find the timestamp of the specified collection in the `monitor/changes` changeset.
+pub fn find_change_timestamp(changeset: &ChangesetResponse, bid: &str, cid: &str) -> Result<u64> {
+    let change = changeset
         .changes
         .iter()
         .find(|&x| x["bucket"] == bid && x["collection"] == cid)
@@ -104,6 +114,17 @@ pub async fn get_latest_change_timestamp(
     Ok(last_modified)
 }
 
+/// Return the number of seconds the server asked clients to back off, if any.
+pub fn get_backoff(headers: &Headers) -> Option<u64> {
+    headers.get("backoff").and_then(|v| v.parse().ok())
+}
+
+/// Return the time of the server (in seconds since epoch), from the `Date` header.
+pub fn get_server_time(headers: &Headers) -> Option<u64> {
+    let date = httpdate::parse_http_date(headers.get("date")?).ok()?;
+    date.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs())
+}
+
 /// Fetches the collection content from the server.
 pub async fn get_changeset(
     requester: &'_ (dyn Requester + 'static),
@@ -122,7 +143,7 @@ pub async fn get_changeset(
     let mut changeset: ChangesetResponse = serde_json::from_slice(&response.body)?;
 
     // Check if server is indicating to clients to back-off.
-    changeset.backoff = response.headers.get("backoff").and_then(|v| v.parse().ok());
+    changeset.backoff = get_backoff(&response.headers);
 
     Ok(changeset)
 }
@@ -262,15 +283,20 @@ async fn _request_resource(
         // See https://docs.kinto-storage.org/en/stable/api/1.x/errors.html#error-responses
         let info: ErrorResponse = match serde_json::from_slice(&response.body) {
             Ok(v) => v,
-            Err(_) => return Err(KintoError::UnexpectedResponse { url, response }),
+            Err(_) => {
+                return Err(KintoError::UnexpectedResponse {
+                    url,
+                    response: Box::new(response),
+                })
+            }
         };
 
         // Error due to the client. The request must be modified.
         if response.is_client_error() {
             return Err(KintoError::ClientRequestError {
                 url,
-                response,
-                info,
+                response: Box::new(response),
+                info: Box::new(info),
             });
         }
 
@@ -278,13 +304,13 @@ async fn _request_resource(
             let retry_after = response
                 .headers
                 .get("retry-after")
-                .map_or_else(|| None, |v| v.parse::<u64>().ok());
+                .and_then(|v| parse_retry_after(v));
 
             return Err(KintoError::ServerError {
                 method,
                 url,
-                response,
-                info,
+                response: Box::new(response),
+                info: Box::new(info),
                 retry_after,
             });
         }
@@ -302,7 +328,7 @@ async fn _request_resource(
 #[cfg(test)]
 mod tests {
     use super::{
-        delete_record, get_changeset, get_latest_change_timestamp, patch_collection, put_record,
+        delete_record, find_change_timestamp, get_changeset, patch_collection, put_record,
         KintoError, KintoObject,
     };
     use crate::client::net::{Headers, Method, Requester, TestHttpClient, TestResponse};
@@ -313,6 +339,16 @@ mod tests {
         let _ = env_logger::builder().is_test(true).try_init();
     }
 
+    async fn get_latest_change_timestamp(
+        requester: &'_ (dyn Requester + 'static),
+        server: &str,
+        bid: &str,
+        cid: &str,
+    ) -> Result<u64, KintoError> {
+        let changes = get_changeset(requester, server, "monitor", "changes", 0, None).await?;
+        find_change_timestamp(&changes, bid, cid)
+    }
+
     #[tokio::test]
     async fn test_fetch() {
         init();
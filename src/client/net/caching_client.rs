@@ -0,0 +1,371 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use super::{Headers, Method, Requester, Response, StreamingResponse};
+
+use async_trait::async_trait;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// Maximum number of responses kept in cache.
+const MAX_CACHED_RESPONSES: usize = 64;
+/// Maximum size of the body of the responses kept in cache.
+const MAX_CACHED_BODY_SIZE: usize = 1024 * 1024;
+
+#[derive(Debug)]
+struct CachedResponse {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    status: u16,
+    body: Vec<u8>,
+}
+
+#[derive(Debug, Default)]
+struct Cache {
+    responses: HashMap<String, CachedResponse>,
+    // URLs in insertion order, to remove the oldest responses first.
+    urls: VecDeque<String>,
+}
+
+impl Cache {
+    fn insert(&mut self, url: String, response: CachedResponse) {
+        if self.responses.insert(url.clone(), response).is_none() {
+            self.urls.push_back(url);
+        }
+        while self.urls.len() > MAX_CACHED_RESPONSES {
+            if let Some(oldest) = self.urls.pop_front() {
+                self.responses.remove(&oldest);
+            }
+        }
+    }
+}
+
+fn find_header<'a>(headers: &'a Headers, name: &str) -> Option<&'a String> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value)
+}
+
+/// An HTTP client that keeps the successful `GET` responses of another client in memory,
+/// and revalidates them with conditional requests.
+///
+/// Responses with an `ETag` or `Last-Modified` header are kept. When the same URL is
+/// requested again, the `If-None-Match` and `If-Modified-Since` headers are sent, and
+/// if the server replies with `304 Not Modified`, the cached body is returned instead.
+///
+/// Both [`Requester::get`] and `GET` requests of [`Requester::request_json`] (eg. changesets)
+/// are cached, unless they have an `Authorization` header. Conditional requests are
+/// performed with [`Requester::get_with_headers`] and [`Requester::request_json`] respectively.
+///
+/// See also [`ClientBuilder::http_cache`](crate::client::ClientBuilder::http_cache).
+pub struct CachingClient {
+    inner: Box<dyn Requester + 'static>,
+    cache: Mutex<Cache>,
+}
+
+impl std::fmt::Debug for CachingClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CachingClient")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CachingClient {
+    pub fn new(inner: Box<dyn Requester + 'static>) -> Self {
+        Self {
+            inner,
+            cache: Mutex::new(Cache::default()),
+        }
+    }
+
+    /// Perform a `GET` request, conditional if the response is cached.
+    ///
+    /// With `json`, the request is performed with [`Requester::request_json`].
+    async fn cached_get(
+        &self,
+        url: url::Url,
+        headers: Headers,
+        json: bool,
+    ) -> Result<Response, ()> {
+        let key = url.to_string();
+        let validators = self
+            .cache
+            .lock()
+            .unwrap()
+            .responses
+            .get(&key)
+            .map(|cached| (cached.etag.clone(), cached.last_modified.clone()));
+
+        let mut conditional_headers = headers.clone();
+        if let Some((etag, last_modified)) = &validators {
+            if let Some(etag) = etag {
+                conditional_headers.insert("If-None-Match".to_string(), etag.clone());
+            }
+            if let Some(last_modified) = last_modified {
+                conditional_headers.insert("If-Modified-Since".to_string(), last_modified.clone());
+            }
+        }
+        let mut response = self.send(url.clone(), conditional_headers, json).await?;
+
+        if response.status == 304 && validators.is_some() {
+            if let Some(cached) = self.cached_response(&key, response.headers) {
+                return Ok(cached);
+            }
+            // The cached response was evicted in the meantime: request the whole body.
+            log::debug!("{} was evicted from the cache, request it again.", key);
+            response = self.send(url, headers, json).await?;
+        }
+        self.store(key, &response);
+        Ok(response)
+    }
+
+    async fn send(&self, url: url::Url, headers: Headers, json: bool) -> Result<Response, ()> {
+        if json {
+            self.inner
+                .request_json(Method::GET, url, vec![], headers)
+                .await
+        } else if headers.is_empty() {
+            self.inner.get(url).await
+        } else {
+            self.inner.get_with_headers(url, headers).await
+        }
+    }
+
+    /// Return the cached response for a `304 Not Modified` response, if still cached.
+    fn cached_response(&self, key: &str, mut headers: Headers) -> Option<Response> {
+        let cache = self.cache.lock().unwrap();
+        let cached = cache.responses.get(key)?;
+        log::debug!("{} was not modified, use cached response.", key);
+        // Keep the headers of the new response, like `Backoff`, but not its date
+        // since the body is not fresh.
+        headers.retain(|name, _| !name.eq_ignore_ascii_case("date"));
+        for (name, value) in &[
+            ("etag", &cached.etag),
+            ("last-modified", &cached.last_modified),
+        ] {
+            if let (None, Some(value)) = (find_header(&headers, name), value) {
+                headers.insert(name.to_string(), value.clone());
+            }
+        }
+        Some(Response {
+            status: cached.status,
+            body: cached.body.clone(),
+            headers,
+        })
+    }
+
+    /// Keep a successful response in cache, if it can be revalidated.
+    fn store(&self, key: String, response: &Response) {
+        if !response.is_success() || response.body.len() > MAX_CACHED_BODY_SIZE {
+            return;
+        }
+        let etag = find_header(&response.headers, "etag").cloned();
+        let last_modified = find_header(&response.headers, "last-modified").cloned();
+        if etag.is_some() || last_modified.is_some() {
+            self.cache.lock().unwrap().insert(
+                key,
+                CachedResponse {
+                    etag,
+                    last_modified,
+                    status: response.status,
+                    body: response.body.clone(),
+                },
+            );
+        }
+    }
+}
+
+#[async_trait]
+impl Requester for CachingClient {
+    async fn get(&self, url: url::Url) -> Result<Response, ()> {
+        self.get_with_headers(url, Headers::new()).await
+    }
+
+    async fn get_with_headers(&self, url: url::Url, headers: Headers) -> Result<Response, ()> {
+        self.cached_get(url, headers, false).await
+    }
+
+    async fn get_streaming(&self, url: url::Url) -> Result<StreamingResponse, ()> {
+        self.inner.get_streaming(url).await
+    }
+
+    async fn request_json(
+        &self,
+        method: Method,
+        url: url::Url,
+        data: Vec<u8>,
+        headers: Headers,
+    ) -> Result<Response, ()> {
+        // Authenticated requests are not cached.
+        if method == Method::GET && find_header(&headers, "authorization").is_none() {
+            return self.cached_get(url, headers, true).await;
+        }
+        self.inner.request_json(method, url, data, headers).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CachingClient, MAX_CACHED_RESPONSES};
+    use crate::client::net::{Headers, Method, Requester, Response};
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    /// Respond with a versioned body, and `304` when the client has the current version.
+    #[derive(Debug, Clone)]
+    struct VersionedClient {
+        version: Arc<Mutex<u32>>,
+        requests: Arc<Mutex<Vec<Headers>>>,
+        /// Hold the conditional requests until cleared.
+        hold: Arc<AtomicBool>,
+    }
+
+    impl VersionedClient {
+        fn new() -> Self {
+            Self {
+                version: Arc::new(Mutex::new(1)),
+                requests: Arc::new(Mutex::new(Vec::new())),
+                hold: Arc::new(AtomicBool::new(false)),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Requester for VersionedClient {
+        async fn get(&self, url: url::Url) -> Result<Response, ()> {
+            self.request_json(Method::GET, url, vec![], Headers::new())
+                .await
+        }
+
+        async fn request_json(
+            &self,
+            _method: Method,
+            url: url::Url,
+            _data: Vec<u8>,
+            headers: Headers,
+        ) -> Result<Response, ()> {
+            while headers.contains_key("If-None-Match") && self.hold.load(Ordering::SeqCst) {
+                tokio::task::yield_now().await;
+            }
+            let version = *self.version.lock().unwrap();
+            let etag = format!("\"{}\"", version);
+            let not_modified = headers.get("If-None-Match") == Some(&etag);
+            self.requests.lock().unwrap().push(headers);
+
+            let mut response_headers = Headers::new();
+            response_headers.insert("backoff".to_string(), version.to_string());
+            if url.path() != "/no-etag" {
+                response_headers.insert("etag".to_string(), etag);
+            }
+            Ok(Response {
+                status: if not_modified { 304 } else { 200 },
+                body: if not_modified {
+                    vec![]
+                } else {
+                    format!("version {}", version).into_bytes()
+                },
+                headers: response_headers,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_revalidates_cached_responses() {
+        let server = VersionedClient::new();
+        let client = CachingClient::new(Box::new(server.clone()));
+        let url: url::Url = "https://example.com/cached".parse().unwrap();
+
+        let response = client.get(url.clone()).await.unwrap();
+        assert_eq!(response.body, b"version 1");
+
+        let response = client.get(url.clone()).await.unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"version 1");
+
+        *server.version.lock().unwrap() = 2;
+        let response = client.get(url.clone()).await.unwrap();
+        assert_eq!(response.body, b"version 2");
+        let response = client.get(url.clone()).await.unwrap();
+        assert_eq!(response.body, b"version 2");
+        assert_eq!(response.headers["backoff"], "2");
+        assert_eq!(response.headers["etag"], "\"2\"");
+
+        let requests = server.requests.lock().unwrap();
+        assert!(requests[0].is_empty());
+        assert_eq!(requests[1]["If-None-Match"], "\"1\"");
+        assert_eq!(requests[2]["If-None-Match"], "\"1\"");
+        assert_eq!(requests[3]["If-None-Match"], "\"2\"");
+    }
+
+    #[tokio::test]
+    async fn test_evicted_responses_are_requested_again() {
+        let server = VersionedClient::new();
+        let client = CachingClient::new(Box::new(server.clone()));
+        let url: url::Url = "https://example.com/cached".parse().unwrap();
+        client.get(url.clone()).await.unwrap();
+
+        // The cached response is evicted while it is revalidated.
+        server.hold.store(true, Ordering::SeqCst);
+        let evict = async {
+            for i in 0..MAX_CACHED_RESPONSES {
+                let url = format!("https://example.com/{}", i).parse().unwrap();
+                client.get(url).await.unwrap();
+            }
+            server.hold.store(false, Ordering::SeqCst);
+        };
+        let (response, ()) = tokio::join!(client.get(url), evict);
+
+        let response = response.unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"version 1");
+        let requests = server.requests.lock().unwrap();
+        assert_eq!(requests[requests.len() - 2]["If-None-Match"], "\"1\"");
+        assert!(requests[requests.len() - 1].is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_authenticated_requests_are_not_cached() {
+        let server = VersionedClient::new();
+        let client = CachingClient::new(Box::new(server.clone()));
+        let url: url::Url = "https://example.com/cached".parse().unwrap();
+        let mut headers = Headers::new();
+        headers.insert("Authorization".to_string(), "Bearer abc".to_string());
+
+        for _ in 0..2 {
+            client
+                .request_json(Method::GET, url.clone(), vec![], headers.clone())
+                .await
+                .unwrap();
+            client
+                .request_json(Method::GET, url.clone(), vec![], Headers::new())
+                .await
+                .unwrap();
+        }
+
+        let requests = server.requests.lock().unwrap();
+        assert!(!requests[2].contains_key("If-None-Match"));
+        assert_eq!(requests[3]["If-None-Match"], "\"1\"");
+    }
+
+    #[tokio::test]
+    async fn test_cache_is_bounded() {
+        let client = CachingClient::new(Box::new(VersionedClient::new()));
+        for i in 0..=MAX_CACHED_RESPONSES {
+            let url = format!("https://example.com/{}", i).parse().unwrap();
+            client.get(url).await.unwrap();
+        }
+        client
+            .get("https://example.com/no-etag".parse().unwrap())
+            .await
+            .unwrap();
+
+        let cache = client.cache.lock().unwrap();
+        assert_eq!(cache.responses.len(), MAX_CACHED_RESPONSES);
+        assert!(!cache.responses.contains_key("https://example.com/0"));
+        assert!(!cache.responses.contains_key("https://example.com/no-etag"));
+    }
+}
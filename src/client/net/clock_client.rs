@@ -0,0 +1,222 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use super::{Headers, Method, Requester, Response, ServerClock, StreamingResponse, Url};
+use crate::client::kinto_http::get_server_time;
+use crate::client::signatures::epoch_seconds;
+
+use async_trait::async_trait;
+use std::sync::Mutex;
+
+/// Maximum difference (in seconds) between the server and the local clocks: larger
+/// differences are ignored, and the local clock is used.
+pub(crate) const MAX_CLOCK_SKEW: u64 = 24 * 60 * 60;
+
+/// An HTTP client that estimates the clock skew with the server from the `Date`
+/// header of the changesets of collections fetched by another client.
+///
+/// The `Date` header is not signed: the skew is bounded by [`MAX_CLOCK_SKEW`], so
+/// that expired certificates cannot be accepted for longer.
+pub(crate) struct ClockClient {
+    inner: Box<dyn Requester + 'static>,
+    tolerance: u64,
+    // Difference in seconds between the server and the local clocks, once known.
+    skew: Mutex<Option<i64>>,
+    // Local time, in seconds since epoch.
+    local_clock: fn() -> u64,
+}
+
+impl std::fmt::Debug for ClockClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClockClient")
+            .field("inner", &self.inner)
+            .field("tolerance", &self.tolerance)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ClockClient {
+    pub fn new(inner: Box<dyn Requester + 'static>, tolerance: u64) -> Self {
+        Self {
+            inner,
+            tolerance,
+            skew: Mutex::new(None),
+            local_clock: epoch_seconds,
+        }
+    }
+
+    /// Sample the server time from a response, only if it is a successful response
+    /// to the changeset of a collection.
+    ///
+    /// Responses served from a cache do not have a `Date` header.
+    fn observe(&self, url: &Url, status: u16, headers: &Headers) {
+        if !(200..300).contains(&status) || !is_collection_changeset(url) {
+            return;
+        }
+        if let Some(server_time) = get_server_time(headers) {
+            let skew = server_time as i64 - (self.local_clock)() as i64;
+            if skew.unsigned_abs() > MAX_CLOCK_SKEW {
+                log::warn!("Ignore the server time, {} seconds away.", skew);
+                return;
+            }
+            *self.skew.lock().unwrap() = Some(skew);
+        }
+    }
+}
+
+/// Whether the URL is the changeset of a collection, other than `monitor/changes`.
+fn is_collection_changeset(url: &Url) -> bool {
+    let segments: Vec<&str> = url.path_segments().map(|s| s.collect()).unwrap_or_default();
+    matches!(
+        segments.as_slice(),
+        [.., "buckets", bid, "collections", _, "changeset"] if *bid != "monitor"
+    )
+}
+
+#[async_trait]
+impl Requester for ClockClient {
+    async fn get(&self, url: Url) -> Result<Response, ()> {
+        self.inner.get(url).await
+    }
+
+    async fn get_with_headers(&self, url: Url, headers: Headers) -> Result<Response, ()> {
+        self.inner.get_with_headers(url, headers).await
+    }
+
+    async fn get_streaming(&self, url: Url) -> Result<StreamingResponse, ()> {
+        self.inner.get_streaming(url).await
+    }
+
+    async fn request_json(
+        &self,
+        method: Method,
+        url: Url,
+        data: Vec<u8>,
+        headers: Headers,
+    ) -> Result<Response, ()> {
+        // Changesets are fetched with this method, see `get_changeset()`.
+        let changeset_url = if method == Method::GET {
+            Some(url.clone())
+        } else {
+            None
+        };
+        let response = self.inner.request_json(method, url, data, headers).await?;
+        if let Some(url) = changeset_url {
+            self.observe(&url, response.status, &response.headers);
+        }
+        Ok(response)
+    }
+
+    fn server_clock(&self) -> ServerClock {
+        let local = (self.local_clock)();
+        let now = match *self.skew.lock().unwrap() {
+            Some(skew) => (local as i64 + skew).max(0) as u64,
+            None => local,
+        };
+        ServerClock {
+            now,
+            tolerance: self.tolerance,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClockClient, MAX_CLOCK_SKEW};
+    use crate::client::net::{Headers, Method, Requester, Response};
+    use async_trait::async_trait;
+
+    /// `Wed, 21 Oct 2015 07:28:00 GMT`
+    const SERVER_TIME: u64 = 1445412480;
+
+    #[derive(Debug)]
+    struct DateClient(&'static str);
+
+    #[async_trait]
+    impl Requester for DateClient {
+        async fn get(&self, url: url::Url) -> Result<Response, ()> {
+            self.request_json(Method::GET, url, vec![], Headers::new())
+                .await
+        }
+
+        async fn request_json(
+            &self,
+            _method: Method,
+            _url: url::Url,
+            _data: Vec<u8>,
+            _headers: Headers,
+        ) -> Result<Response, ()> {
+            let mut headers = Headers::new();
+            headers.insert("date".to_string(), self.0.to_string());
+            Ok(Response {
+                status: 200,
+                body: vec![],
+                headers,
+            })
+        }
+    }
+
+    fn client_at(local_clock: fn() -> u64, date: &'static str) -> ClockClient {
+        ClockClient {
+            local_clock,
+            ..ClockClient::new(Box::new(DateClient(date)), 300)
+        }
+    }
+
+    async fn fetch(client: &ClockClient, url: &str) {
+        client
+            .request_json(Method::GET, url.parse().unwrap(), vec![], Headers::new())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_server_clock() {
+        const CHANGESET: &str = "https://example.com/v1/buckets/main/collections/cid/changeset";
+        const DATE: &str = "Wed, 21 Oct 2015 07:28:00 GMT";
+
+        // The local clock is used until the server time is known.
+        let client = client_at(|| SERVER_TIME - 3600, "invalid");
+        fetch(&client, CHANGESET).await;
+        assert_eq!(client.server_clock().now, SERVER_TIME - 3600);
+
+        let client = client_at(|| SERVER_TIME - 3600, DATE);
+        fetch(&client, CHANGESET).await;
+        let clock = client.server_clock();
+        assert_eq!(clock.now, SERVER_TIME);
+        assert_eq!(clock.tolerance, 300);
+
+        // Only the changesets of collections are trusted.
+        let client = client_at(|| SERVER_TIME - 3600, DATE);
+        for url in &[
+            "https://example.com/v1/buckets/monitor/collections/changes/changeset",
+            "https://example.com/v1/",
+            "https://example.com/attachments/file.bin",
+        ] {
+            fetch(&client, url).await;
+            client.get(url.parse().unwrap()).await.unwrap();
+        }
+        assert_eq!(client.server_clock().now, SERVER_TIME - 3600);
+    }
+
+    #[tokio::test]
+    async fn test_server_clock_skew_is_bounded() {
+        const CHANGESET: &str = "https://example.com/v1/buckets/main/collections/cid/changeset";
+        const DATE: &str = "Wed, 21 Oct 2015 07:28:00 GMT";
+
+        let client = client_at(|| SERVER_TIME + MAX_CLOCK_SKEW, DATE);
+        fetch(&client, CHANGESET).await;
+        assert_eq!(client.server_clock().now, SERVER_TIME);
+
+        // Larger differences are ignored.
+        for local in &[
+            || SERVER_TIME + MAX_CLOCK_SKEW + 1,
+            || SERVER_TIME - 10 * 365 * 86400,
+        ] {
+            let client = client_at(*local, DATE);
+            fetch(&client, CHANGESET).await;
+            assert_eq!(client.server_clock().now, local());
+        }
+    }
+}
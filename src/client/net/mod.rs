@@ -6,13 +6,26 @@ use std::collections::HashMap;
 
 use async_trait::async_trait;
 
+use crate::client::signatures::epoch_seconds;
+
+mod caching_client;
+mod clock_client;
 mod dummy_client;
+#[cfg(feature = "reqwest_client")]
+mod reqwest_client;
+mod retry_client;
 #[cfg(test)]
 mod test_client;
 #[cfg(feature = "viaduct_client")]
 mod viaduct_client;
 
+pub use caching_client::CachingClient;
+pub(crate) use clock_client::ClockClient;
 pub(crate) use dummy_client::DummyClient;
+#[cfg(feature = "reqwest_client")]
+pub use reqwest_client::ReqwestClient;
+pub(crate) use retry_client::parse_retry_after;
+pub use retry_client::{RetryClient, RetryPolicy};
 #[cfg(test)]
 pub(crate) use test_client::{TestHttpClient, TestResponse};
 #[cfg(feature = "viaduct_client")]
@@ -64,6 +77,76 @@ impl Response {
     }
 }
 
+/// The body of a response, received chunk by chunk.
+#[async_trait]
+pub trait BodyStream: Send {
+    /// Return the next chunk of the body, or `None` once it was entirely received.
+    async fn next_chunk(&mut self) -> Result<Option<Vec<u8>>, ()>;
+}
+
+/// A body that was already entirely received.
+struct BufferedBody(Option<Vec<u8>>);
+
+#[async_trait]
+impl BodyStream for BufferedBody {
+    async fn next_chunk(&mut self) -> Result<Option<Vec<u8>>, ()> {
+        Ok(self.0.take())
+    }
+}
+
+/// A response whose body is received progressively.
+pub struct StreamingResponse {
+    /// The HTTP status code of the response.
+    pub status: u16,
+
+    // The body of the response.
+    pub body: Box<dyn BodyStream>,
+
+    // The headers of the response.
+    pub headers: Headers,
+}
+
+impl StreamingResponse {
+    /// Whether or not the response code represents HTTP success.
+    pub fn is_success(&self) -> bool {
+        (200..=299).contains(&self.status)
+    }
+
+    /// Whether or not the response code represents HTTP server error.
+    pub fn is_server_error(&self) -> bool {
+        (500..=599).contains(&self.status)
+    }
+}
+
+impl std::fmt::Debug for StreamingResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StreamingResponse")
+            .field("status", &self.status)
+            .field("headers", &self.headers)
+            .finish()
+    }
+}
+
+impl From<Response> for StreamingResponse {
+    fn from(response: Response) -> Self {
+        Self {
+            status: response.status,
+            body: Box::new(BufferedBody(Some(response.body))),
+            headers: response.headers,
+        }
+    }
+}
+
+/// The clock used to check the validity of certificates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ServerClock {
+    /// Current time of the server, in seconds since epoch.
+    pub now: u64,
+    /// Difference (in seconds) tolerated between the server time and the validity
+    /// period of certificates.
+    pub tolerance: u64,
+}
+
 /// A description of a component used to perform an HTTP request.
 #[async_trait]
 pub trait Requester: std::fmt::Debug + Send + Sync {
@@ -74,6 +157,40 @@ pub trait Requester: std::fmt::Debug + Send + Sync {
     /// * `url` - the URL path to perform the HTTP GET on.
     async fn get(&self, url: Url) -> Result<Response, ()>;
 
+    /// Perform a GET request with the specified headers, like the conditional request
+    /// headers `If-None-Match` and `If-Modified-Since`.
+    ///
+    /// By default, the request is performed with [`Requester::request_json`].
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - the URL path to perform the HTTP GET on.
+    /// * `headers` - the headers to send.
+    async fn get_with_headers(&self, url: Url, headers: Headers) -> Result<Response, ()> {
+        self.request_json(Method::GET, url, vec![], headers).await
+    }
+
+    /// Perform a GET request, and return the response before its body is received.
+    ///
+    /// By default, the whole body is received with [`Requester::get`] first.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - the URL path to perform the HTTP GET on.
+    async fn get_streaming(&self, url: Url) -> Result<StreamingResponse, ()> {
+        Ok(self.get(url).await?.into())
+    }
+
+    /// Return the clock of the server, as estimated from previous responses.
+    ///
+    /// By default, the local time is used, without tolerance.
+    fn server_clock(&self) -> ServerClock {
+        ServerClock {
+            now: epoch_seconds(),
+            tolerance: 0,
+        }
+    }
+
     /// Perform a JSON request toward the needed resource.
     ///
     /// # Arguments
@@ -0,0 +1,232 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use super::{BodyStream, Headers, Method, Requester, Response, StreamingResponse};
+
+use async_trait::async_trait;
+use std::time::Duration;
+
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// An asynchronous HTTP client that uses [reqwest](https://docs.rs/reqwest).
+///
+/// Connections are pooled, and responses are decompressed transparently.
+///
+/// # Examples
+/// ```rust
+/// # use remote_settings_client::Client;
+/// # use remote_settings_client::client::net::ReqwestClient;
+/// let client = Client::builder()
+///   .collection_name("cid")
+///   .http_client(Box::new(ReqwestClient::new()))
+///   .build();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ReqwestClient {
+    client: reqwest::Client,
+}
+
+impl ReqwestClient {
+    /// Creates a client with the default timeouts (10 seconds to connect, and
+    /// 30 seconds between reads).
+    pub fn new() -> Self {
+        Self::with_timeouts(DEFAULT_CONNECT_TIMEOUT, DEFAULT_READ_TIMEOUT)
+    }
+
+    /// Creates a client with the specified timeouts.
+    ///
+    /// The read timeout applies to each read, so that large attachments can be
+    /// streamed without being interrupted.
+    ///
+    /// # Panics
+    /// If the TLS backend cannot be initialized, like [`reqwest::Client::new`].
+    pub fn with_timeouts(connect_timeout: Duration, read_timeout: Duration) -> Self {
+        let client = reqwest::Client::builder()
+            .connect_timeout(connect_timeout)
+            .read_timeout(read_timeout)
+            .gzip(true)
+            .build()
+            .expect("reqwest client could not be initialized");
+        ReqwestClient { client }
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        url: url::Url,
+        data: Vec<u8>,
+        headers: Headers,
+    ) -> Result<reqwest::Response, ()> {
+        let method = match method {
+            Method::DELETE => reqwest::Method::DELETE,
+            Method::GET => reqwest::Method::GET,
+            Method::PATCH => reqwest::Method::PATCH,
+            Method::PUT => reqwest::Method::PUT,
+            Method::POST => reqwest::Method::POST,
+        };
+        let mut request = self.client.request(method.clone(), url);
+        if !data.is_empty() {
+            request = request
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(data);
+        }
+        for (key, value) in headers {
+            request = request.header(key, value);
+        }
+
+        request.send().await.map_err(|e| {
+            log::error!(
+                "ReqwestClient - unable to submit {:?} request. {:?}",
+                method,
+                e.to_string()
+            );
+        })
+    }
+}
+
+impl Default for ReqwestClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<reqwest::Client> for ReqwestClient {
+    fn from(client: reqwest::Client) -> Self {
+        ReqwestClient { client }
+    }
+}
+
+fn response_headers(response: &reqwest::Response) -> Headers {
+    let mut headers = Headers::new();
+    for (name, value) in response.headers() {
+        if let Ok(value) = value.to_str() {
+            headers
+                .entry(name.to_string())
+                .or_insert_with(|| value.to_string());
+        }
+    }
+    headers
+}
+
+struct ReqwestBody(reqwest::Response);
+
+#[async_trait]
+impl BodyStream for ReqwestBody {
+    async fn next_chunk(&mut self) -> Result<Option<Vec<u8>>, ()> {
+        match self.0.chunk().await {
+            Ok(chunk) => Ok(chunk.map(|bytes| bytes.to_vec())),
+            Err(e) => {
+                log::error!("ReqwestClient - unable to read body. {:?}", e.to_string());
+                Err(())
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Requester for ReqwestClient {
+    async fn get(&self, url: url::Url) -> Result<Response, ()> {
+        self.request_json(Method::GET, url, vec![], Headers::default())
+            .await
+    }
+
+    async fn get_streaming(&self, url: url::Url) -> Result<StreamingResponse, ()> {
+        let response = self
+            .send(Method::GET, url, vec![], Headers::default())
+            .await?;
+        Ok(StreamingResponse {
+            status: response.status().as_u16(),
+            headers: response_headers(&response),
+            body: Box::new(ReqwestBody(response)),
+        })
+    }
+
+    async fn request_json(
+        &self,
+        method: Method,
+        url: url::Url,
+        data: Vec<u8>,
+        headers: Headers,
+    ) -> Result<Response, ()> {
+        let response = self.send(method, url, data, headers).await?;
+        let status = response.status().as_u16();
+        let headers = response_headers(&response);
+        let body = response.bytes().await.map_err(|e| {
+            log::error!("ReqwestClient - unable to read body. {:?}", e.to_string());
+        })?;
+
+        Ok(Response {
+            status,
+            body: body.to_vec(),
+            headers,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReqwestClient;
+    use crate::client::net::{Headers, Method, Requester};
+    use httpmock::MockServer;
+
+    #[tokio::test]
+    async fn test_get() {
+        let mock_server = MockServer::start();
+        let mut mock = mock_server.mock(|when, then| {
+            when.path("/v1/");
+            then.status(200)
+                .header("Backoff", "10")
+                .body("{\"project_name\": \"Remote Settings\"}");
+        });
+
+        let client = ReqwestClient::new();
+        let url: url::Url = mock_server.url("/v1/").parse().unwrap();
+        let response = client.get(url.clone()).await.unwrap();
+        assert!(response.is_success());
+        assert_eq!(response.headers["backoff"], "10");
+        assert_eq!(response.body, b"{\"project_name\": \"Remote Settings\"}");
+
+        let mut response = client.get_streaming(url).await.unwrap();
+        assert!(response.is_success());
+        assert_eq!(response.headers["backoff"], "10");
+        let mut body = Vec::new();
+        while let Some(chunk) = response.body.next_chunk().await.unwrap() {
+            body.extend(chunk);
+        }
+        assert_eq!(body, b"{\"project_name\": \"Remote Settings\"}");
+
+        mock.assert_hits(2);
+        mock.delete();
+    }
+
+    #[tokio::test]
+    async fn test_request_json() {
+        let mock_server = MockServer::start();
+        let mut mock = mock_server.mock(|when, then| {
+            when.method("PUT")
+                .path("/v1/record")
+                .header("Content-Type", "application/json")
+                .header("Authorization", "Bearer abc")
+                .body("{}");
+            then.status(201).body("{}");
+        });
+
+        let mut headers = Headers::new();
+        headers.insert("Authorization".to_string(), "Bearer abc".to_string());
+        let response = ReqwestClient::default()
+            .request_json(
+                Method::PUT,
+                mock_server.url("/v1/record").parse().unwrap(),
+                b"{}".to_vec(),
+                headers,
+            )
+            .await
+            .unwrap();
+        mock.assert();
+        mock.delete();
+
+        assert_eq!(response.status, 201);
+    }
+}
@@ -0,0 +1,379 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use super::{Headers, Method, Requester, Response, StreamingResponse};
+
+use async_trait::async_trait;
+use futures_timer::Delay;
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, SystemTime};
+
+/// Policy to retry HTTP requests on transient failures.
+///
+/// Network errors and server errors (HTTP 5xx) are considered transient.
+/// Only `GET` requests are retried.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts for a request (`1` disables retries).
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled at each subsequent attempt.
+    pub base_delay: Duration,
+    /// Maximum delay between two attempts.
+    ///
+    /// If the server asks to retry after a longer delay, the request is not retried.
+    pub max_delay: Duration,
+    /// Randomize delays, to avoid clients retrying all at the same time.
+    pub jitter: bool,
+    /// Wait for the delay indicated by the `Retry-After` response header, if any.
+    pub honor_retry_after: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            jitter: true,
+            honor_retry_after: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Return the delay to wait before the specified attempt (starting at 1 for the first retry),
+    /// or `None` if the request should not be retried.
+    fn delay(&self, attempt: u32, retry_after: Option<u64>) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+        if let (true, Some(secs)) = (self.honor_retry_after, retry_after) {
+            let delay = Duration::from_secs(secs);
+            return if delay <= self.max_delay {
+                Some(delay)
+            } else {
+                None
+            };
+        }
+        let exponential = self
+            .base_delay
+            .checked_mul(2u32.saturating_pow(attempt - 1))
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+        if !self.jitter {
+            return Some(exponential);
+        }
+        // Wait between half and the full delay.
+        let random = RandomState::new().build_hasher().finish();
+        let half = exponential / 2;
+        Some(half + half.mul_f64((random % 1000) as f64 / 1000.0))
+    }
+}
+
+/// Parse the value of a `Retry-After` header, either a number of seconds or an HTTP date,
+/// as a number of seconds to wait.
+pub(crate) fn parse_retry_after(value: &str) -> Option<u64> {
+    if let Ok(secs) = value.trim().parse::<u64>() {
+        return Some(secs);
+    }
+    let date = httpdate::parse_http_date(value.trim()).ok()?;
+    // A date in the past means that the request can be retried right away.
+    Some(
+        date.duration_since(SystemTime::now())
+            .map_or(0, |delay| delay.as_secs()),
+    )
+}
+
+/// The parts of a response that decide whether to retry the request.
+trait Retryable {
+    fn is_server_error(&self) -> bool;
+    fn headers(&self) -> &Headers;
+}
+
+impl Retryable for Response {
+    fn is_server_error(&self) -> bool {
+        Response::is_server_error(self)
+    }
+
+    fn headers(&self) -> &Headers {
+        &self.headers
+    }
+}
+
+impl Retryable for StreamingResponse {
+    fn is_server_error(&self) -> bool {
+        StreamingResponse::is_server_error(self)
+    }
+
+    fn headers(&self) -> &Headers {
+        &self.headers
+    }
+}
+
+/// An HTTP client that retries the requests of another client according to a [`RetryPolicy`].
+///
+/// ```rust
+/// # #[cfg(feature = "viaduct_client")] {
+/// # use remote_settings_client::client::net::{RetryClient, RetryPolicy, ViaductClient};
+/// let http_client = RetryClient::new(
+///     Box::new(ViaductClient),
+///     RetryPolicy {
+///         max_attempts: 3,
+///         ..RetryPolicy::default()
+///     },
+/// );
+/// # }
+/// ```
+///
+/// See also [`ClientBuilder::retry_policy`](crate::client::ClientBuilder::retry_policy).
+#[derive(Debug)]
+pub struct RetryClient {
+    inner: Box<dyn Requester + 'static>,
+    policy: RetryPolicy,
+}
+
+impl RetryClient {
+    pub fn new(inner: Box<dyn Requester + 'static>, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    async fn with_retries<F, Fut, R>(&self, url: &url::Url, send: F) -> Result<R, ()>
+    where
+        F: Fn() -> Fut + Send + Sync,
+        Fut: Future<Output = Result<R, ()>> + Send,
+        R: Retryable + Send,
+    {
+        let mut attempt = 1;
+        loop {
+            let result = send().await;
+            let retry_after = match &result {
+                Ok(response) if !response.is_server_error() => return result,
+                Ok(response) => response
+                    .headers()
+                    .get("retry-after")
+                    .and_then(|v| parse_retry_after(v)),
+                Err(_) => None,
+            };
+            let delay = match self.policy.delay(attempt, retry_after) {
+                Some(delay) => delay,
+                None => return result,
+            };
+            log::warn!(
+                "Request to {} failed (attempt {}/{}), retry in {:?}.",
+                url,
+                attempt,
+                self.policy.max_attempts,
+                delay
+            );
+            Delay::new(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[async_trait]
+impl Requester for RetryClient {
+    async fn get(&self, url: url::Url) -> Result<Response, ()> {
+        self.with_retries(&url, || self.inner.get(url.clone()))
+            .await
+    }
+
+    async fn get_streaming(&self, url: url::Url) -> Result<StreamingResponse, ()> {
+        self.with_retries(&url, || self.inner.get_streaming(url.clone()))
+            .await
+    }
+
+    async fn request_json(
+        &self,
+        method: Method,
+        url: url::Url,
+        data: Vec<u8>,
+        headers: Headers,
+    ) -> Result<Response, ()> {
+        if method != Method::GET {
+            return self.inner.request_json(method, url, data, headers).await;
+        }
+        self.with_retries(&url, || {
+            self.inner
+                .request_json(method, url.clone(), data.clone(), headers.clone())
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_retry_after, RetryClient, RetryPolicy};
+    use crate::client::net::{Headers, Method, Requester, Response};
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+    use std::time::{Duration, SystemTime};
+
+    /// Respond with the specified statuses in order.
+    #[derive(Debug)]
+    struct SequenceClient {
+        statuses: Mutex<Vec<Result<u16, ()>>>,
+        retry_after: Option<String>,
+    }
+
+    impl SequenceClient {
+        fn new(statuses: Vec<Result<u16, ()>>) -> Self {
+            Self {
+                statuses: Mutex::new(statuses.into_iter().rev().collect()),
+                retry_after: None,
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Requester for SequenceClient {
+        async fn get(&self, url: url::Url) -> Result<Response, ()> {
+            self.request_json(Method::GET, url, vec![], Headers::new())
+                .await
+        }
+
+        async fn request_json(
+            &self,
+            _method: Method,
+            _url: url::Url,
+            _data: Vec<u8>,
+            _headers: Headers,
+        ) -> Result<Response, ()> {
+            let status = self.statuses.lock().unwrap().pop().unwrap()?;
+            let mut headers = Headers::new();
+            if let Some(ref v) = self.retry_after {
+                headers.insert("retry-after".to_string(), v.clone());
+            }
+            Ok(Response {
+                status,
+                body: vec![],
+                headers,
+            })
+        }
+    }
+
+    fn policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(10),
+            jitter: false,
+            honor_retry_after: true,
+        }
+    }
+
+    fn url() -> url::Url {
+        url::Url::parse("https://example.com/v1").unwrap()
+    }
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            jitter: false,
+            honor_retry_after: true,
+        };
+        assert_eq!(policy.delay(1, None), Some(Duration::from_secs(1)));
+        assert_eq!(policy.delay(3, None), Some(Duration::from_secs(4)));
+        assert_eq!(policy.delay(9, None), Some(Duration::from_secs(60)));
+        assert_eq!(policy.delay(10, None), None);
+        // Retry-After is honored, unless it is too long.
+        assert_eq!(policy.delay(1, Some(30)), Some(Duration::from_secs(30)));
+        assert_eq!(policy.delay(1, Some(3600)), None);
+
+        let policy = RetryPolicy {
+            honor_retry_after: false,
+            jitter: true,
+            ..policy
+        };
+        let delay = policy.delay(3, Some(3600)).unwrap();
+        assert!(delay >= Duration::from_secs(2) && delay <= Duration::from_secs(4));
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("120"), Some(120));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(0));
+        let later = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(3600));
+        let secs = parse_retry_after(&later).unwrap();
+        assert!(secs > 3500 && secs <= 3600);
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_failures() {
+        let client = RetryClient::new(
+            Box::new(SequenceClient::new(vec![Err(()), Ok(503), Ok(200)])),
+            policy(3),
+        );
+        assert_eq!(client.get(url()).await.unwrap().status, 200);
+
+        let client = RetryClient::new(
+            Box::new(SequenceClient::new(vec![Ok(503), Ok(200)])),
+            policy(3),
+        );
+        assert_eq!(client.get_streaming(url()).await.unwrap().status, 200);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let client = RetryClient::new(
+            Box::new(SequenceClient::new(vec![Ok(500), Ok(502), Ok(200)])),
+            policy(2),
+        );
+        assert_eq!(client.get(url()).await.unwrap().status, 502);
+
+        let client = RetryClient::new(
+            Box::new(SequenceClient::new(vec![Err(()), Err(())])),
+            policy(2),
+        );
+        assert!(client.get(url()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_does_not_retry_client_errors_or_writes() {
+        let client = RetryClient::new(
+            Box::new(SequenceClient::new(vec![Ok(404), Ok(200)])),
+            policy(3),
+        );
+        assert_eq!(client.get(url()).await.unwrap().status, 404);
+
+        let client = RetryClient::new(
+            Box::new(SequenceClient::new(vec![Ok(503), Ok(200)])),
+            policy(3),
+        );
+        let response = client
+            .request_json(Method::PUT, url(), vec![], Headers::new())
+            .await
+            .unwrap();
+        assert_eq!(response.status, 503);
+    }
+
+    #[tokio::test]
+    async fn test_does_not_retry_if_retry_after_too_long() {
+        let mut inner = SequenceClient::new(vec![Ok(503), Ok(200)]);
+        inner.retry_after = Some("3600".to_string());
+        let client = RetryClient::new(Box::new(inner), policy(3));
+        assert_eq!(client.get(url()).await.unwrap().status, 503);
+
+        let mut inner = SequenceClient::new(vec![Ok(503), Ok(200)]);
+        inner.retry_after = Some(httpdate::fmt_http_date(
+            SystemTime::now() + Duration::from_secs(3600),
+        ));
+        let client = RetryClient::new(Box::new(inner), policy(3));
+        assert_eq!(client.get(url()).await.unwrap().status, 503);
+    }
+
+    #[tokio::test]
+    async fn test_retries_after_http_date() {
+        let mut inner = SequenceClient::new(vec![Ok(503), Ok(200)]);
+        inner.retry_after = Some("Wed, 21 Oct 2015 07:28:00 GMT".to_string());
+        let client = RetryClient::new(Box::new(inner), policy(3));
+        assert_eq!(client.get(url()).await.unwrap().status, 200);
+    }
+}
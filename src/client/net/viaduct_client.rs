@@ -8,6 +8,9 @@ use async_trait::async_trait;
 use viaduct::{header_names, Request as ViaductRequest};
 
 /// An HTTP client that uses [Viaduct](https://github.com/mozilla/application-services/tree/main/components/viaduct).
+///
+/// Viaduct does not stream responses: the body of [`Requester::get_streaming`]
+/// responses is entirely received in memory before it is returned.
 #[derive(Debug)]
 pub struct ViaductClient;
 
@@ -0,0 +1,407 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use super::backoff::{check_backoff, set_backoff};
+use super::kinto_http::{get_backoff, get_changeset, KintoError};
+use super::{net, Client, ClientError, DummyStorage, Storage, SyncResult, DEFAULT_SERVER_URL};
+use log::{debug, info};
+use std::collections::HashMap;
+
+/// Outcome of a [`RemoteSettings::sync`] call.
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    /// Timestamp of the `monitor/changes` endpoint at the time of the poll.
+    pub timestamp: u64,
+    /// Result of the synchronization of each changed collection, keyed by `{bid}/{cid}`.
+    ///
+    /// Collections that did not change since the previous poll are not listed.
+    pub collections: HashMap<String, Result<SyncResult, ClientError>>,
+}
+
+impl SyncReport {
+    /// Return true if every changed collection was synchronized successfully.
+    pub fn is_success(&self) -> bool {
+        self.collections.values().all(|r| r.is_ok())
+    }
+}
+
+/// Manager to synchronize several collections at once.
+///
+/// Instead of polling the `monitor/changes` endpoint once per collection, the
+/// manager fetches it once, and only synchronizes the collections whose timestamp
+/// changed since the previous poll.
+///
+/// # Examples
+/// ```no_run
+/// # use remote_settings_client::{Client, RemoteSettings};
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut remote_settings = RemoteSettings::builder()
+///   .client(Client::builder().collection_name("cid1").build()?)
+///   .client(Client::builder().collection_name("cid2").build()?)
+///   .build()?;
+///
+/// let report = remote_settings.sync().await?;
+/// for (cid, result) in &report.collections {
+///     match result {
+///         Ok(result) => println!("{}: {} new records", cid, result.created.len()),
+///         Err(err) => println!("{}: {}", cid, err),
+///     }
+/// }
+///
+/// let client = remote_settings.client("main", "cid1").unwrap();
+/// let records = client.get().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Builder, Debug)]
+#[builder(pattern = "owned")] // No clone because of Box<dyn...>
+pub struct RemoteSettings {
+    #[builder(setter(into), default = "DEFAULT_SERVER_URL.to_owned()")]
+    server_url: String,
+    #[builder(default = "Box::new(net::DummyClient)")]
+    http_client: Box<dyn net::Requester + 'static>,
+    /// Where the backoff requested by the server is persisted, so that it is still
+    /// respected after a restart (eg. a [`FileStorage`](crate::FileStorage) in the
+    /// folder of the clients).
+    #[builder(default = "Box::new(DummyStorage {})")]
+    storage: Box<dyn Storage>,
+    #[builder(setter(custom), default = "Vec::new()")]
+    clients: Vec<Client>,
+    #[builder(private, default = "None")]
+    last_poll: Option<u64>,
+    #[builder(private, default = "None")]
+    backoff_until: Option<u64>,
+}
+
+impl RemoteSettingsBuilder {
+    /// Add a client to the set of synchronized collections.
+    pub fn client(mut self, client: Client) -> Self {
+        self.clients.get_or_insert_with(Vec::new).push(client);
+        self
+    }
+}
+
+impl RemoteSettings {
+    /// Creates a `RemoteSettingsBuilder` to configure a `RemoteSettings`.
+    pub fn builder() -> RemoteSettingsBuilder {
+        RemoteSettingsBuilder::default()
+    }
+
+    /// Add a client to the set of synchronized collections.
+    ///
+    /// The next call to [`RemoteSettings::sync`] will poll all changes, in
+    /// order to synchronize the new collection too.
+    pub fn add_client(&mut self, client: Client) {
+        self.clients.push(client);
+        self.last_poll = None;
+    }
+
+    /// Return the client of the specified collection, if any.
+    pub fn client(&mut self, bid: &str, cid: &str) -> Option<&mut Client> {
+        self.clients
+            .iter_mut()
+            .find(|c| c.bucket_name == bid && c.collection_name == cid)
+    }
+
+    /// Synchronize the collections that changed since the last poll.
+    ///
+    /// # Behaviour
+    /// * Fetch the list of changed collections from the `monitor/changes` endpoint,
+    ///   since the timestamp of the last successful poll;
+    /// * Synchronize each changed collection that has a client;
+    /// * If every synchronization succeeded, remember the poll timestamp for the next call.
+    ///
+    /// A failed collection synchronization does not interrupt the others, and
+    /// is reported in the returned [`SyncReport`].
+    ///
+    /// # Errors
+    /// If an error occurs while polling for changes, a [`ClientError`] is returned.
+    pub async fn sync(&mut self) -> Result<SyncReport, ClientError> {
+        self.check_sync_state()?;
+
+        debug!("Poll changes since {:?}.", self.last_poll);
+        // When we fetch the monitor/changes endpoint manually (ie. not from a push notification)
+        // we cannot know the current timestamp, and use 0 arbitrarily.
+        let changeset = get_changeset(
+            self.http_client.as_ref(),
+            &self.server_url,
+            "monitor",
+            "changes",
+            0,
+            self.last_poll,
+        )
+        .await;
+        let backoff_secs = match &changeset {
+            Ok(changeset) => changeset.backoff,
+            Err(err) => err.response().and_then(|r| get_backoff(&r.headers)),
+        };
+        set_backoff(
+            &mut self.backoff_until,
+            self.storage.as_mut(),
+            &self.server_url,
+            backoff_secs,
+        )?;
+        let changeset = changeset?;
+
+        let mut report = SyncReport {
+            timestamp: changeset.timestamp,
+            ..SyncReport::default()
+        };
+
+        for change in &changeset.changes {
+            let (bid, cid) = match (change["bucket"].as_str(), change["collection"].as_str()) {
+                (Some(bid), Some(cid)) => (bid, cid),
+                _ => continue,
+            };
+            let client = match self.client(bid, cid) {
+                Some(client) => client,
+                None => continue,
+            };
+            let key = format!("{}/{}", bid, cid);
+            let result = match change["last_modified"].as_u64() {
+                Some(last_modified) => {
+                    info!("{}: changed (last_modified={}).", key, last_modified);
+                    client.sync_with_changes(last_modified).await
+                }
+                None => Err(KintoError::InvalidChangesetTimestamp(
+                    change["last_modified"].to_string(),
+                )
+                .into()),
+            };
+            report.collections.insert(key, result);
+        }
+
+        // If a collection failed, we will try again on the next poll.
+        if report.is_success() {
+            self.last_poll = Some(changeset.timestamp);
+        }
+
+        Ok(report)
+    }
+
+    fn check_sync_state(&mut self) -> Result<(), ClientError> {
+        check_backoff(
+            &mut self.backoff_until,
+            self.storage.as_mut(),
+            &self.server_url,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RemoteSettings;
+    use crate::client::net::{Headers, Method, Requester, TestHttpClient, TestResponse};
+    use crate::client::{Client, ClientError, FileStorage, MemoryStorage};
+    use serde_json::json;
+
+    const FAKE_SERVER: &str = "https://www.example.com/v1";
+
+    fn init() {
+        let _ = env_logger::builder().is_test(true).try_init();
+    }
+
+    fn changeset_response(url: String, body: serde_json::Value) -> TestResponse {
+        TestResponse {
+            request_method: Method::GET,
+            request_url: url,
+            response_status: 200,
+            response_body: body.to_string().as_bytes().to_vec(),
+            response_headers: Headers::new(),
+        }
+    }
+
+    fn test_client() -> Box<dyn Requester + 'static> {
+        Box::new(TestHttpClient::new(vec![
+            changeset_response(
+                format!(
+                    "{}/buckets/monitor/collections/changes/changeset?_expected=0",
+                    FAKE_SERVER
+                ),
+                json!({
+                    "metadata": {},
+                    "changes": [{
+                        "id": "a",
+                        "last_modified": 42,
+                        "bucket": "main",
+                        "collection": "cid1"
+                    }, {
+                        "id": "b",
+                        "last_modified": 43,
+                        "bucket": "main",
+                        "collection": "cid2"
+                    }, {
+                        "id": "c",
+                        "last_modified": 44,
+                        "bucket": "main",
+                        "collection": "not-synced"
+                    }],
+                    "timestamp": 44
+                }),
+            ),
+            changeset_response(
+                format!(
+                    "{}/buckets/monitor/collections/changes/changeset?_expected=0&_since=%2244%22",
+                    FAKE_SERVER
+                ),
+                json!({
+                    "metadata": {},
+                    "changes": [{
+                        "id": "b",
+                        "last_modified": 50,
+                        "bucket": "main",
+                        "collection": "cid2"
+                    }],
+                    "timestamp": 50
+                }),
+            ),
+            changeset_response(
+                format!(
+                    "{}/buckets/main/collections/cid1/changeset?_expected=42",
+                    FAKE_SERVER
+                ),
+                json!({
+                    "metadata": {},
+                    "changes": [{"id": "record-1", "last_modified": 42}],
+                    "timestamp": 42
+                }),
+            ),
+            changeset_response(
+                format!(
+                    "{}/buckets/main/collections/cid2/changeset?_expected=43",
+                    FAKE_SERVER
+                ),
+                json!({
+                    "metadata": {},
+                    "changes": [{"id": "record-2", "last_modified": 43}],
+                    "timestamp": 43
+                }),
+            ),
+            changeset_response(
+                format!(
+                    "{}/buckets/main/collections/cid2/changeset?_expected=50&_since=%2243%22",
+                    FAKE_SERVER
+                ),
+                json!({
+                    "metadata": {},
+                    "changes": [{"id": "record-3", "last_modified": 50}],
+                    "timestamp": 50
+                }),
+            ),
+        ]))
+    }
+
+    fn client_for(cid: &str) -> Client {
+        Client::builder()
+            .server_url(FAKE_SERVER)
+            .http_client(test_client())
+            .collection_name(cid)
+            .storage(Box::new(MemoryStorage::new()))
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_sync_only_changed_collections() {
+        init();
+
+        let mut remote_settings = RemoteSettings::builder()
+            .server_url(FAKE_SERVER)
+            .http_client(test_client())
+            .client(client_for("cid1"))
+            .client(client_for("cid2"))
+            .build()
+            .unwrap();
+
+        let report = remote_settings.sync().await.unwrap();
+        assert!(report.is_success());
+        assert_eq!(report.timestamp, 44);
+        assert_eq!(report.collections.len(), 2);
+        let result = report.collections["main/cid1"].as_ref().unwrap();
+        assert_eq!(result.collection.timestamp, 42);
+        assert_eq!(result.created.len(), 1);
+        let result = report.collections["main/cid2"].as_ref().unwrap();
+        assert_eq!(result.collection.timestamp, 43);
+
+        // Second poll uses `_since` and only syncs `cid2`.
+        let report = remote_settings.sync().await.unwrap();
+        assert_eq!(report.timestamp, 50);
+        assert_eq!(report.collections.len(), 1);
+        let result = report.collections["main/cid2"].as_ref().unwrap();
+        assert_eq!(result.collection.records.len(), 2);
+        assert_eq!(result.created[0].id(), "record-3");
+
+        let client = remote_settings.client("main", "cid1").unwrap();
+        assert_eq!(client.get().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_sync_reports_collection_errors() {
+        init();
+
+        let mut remote_settings = RemoteSettings::builder()
+            .server_url(FAKE_SERVER)
+            .http_client(test_client())
+            .client(client_for("cid1"))
+            .client(
+                Client::builder()
+                    .server_url(FAKE_SERVER)
+                    .collection_name("cid2")
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+
+        let report = remote_settings.sync().await.unwrap();
+        assert!(!report.is_success());
+        assert!(report.collections["main/cid1"].is_ok());
+        assert!(matches!(
+            report.collections["main/cid2"],
+            Err(ClientError::APIError(_))
+        ));
+
+        // Poll timestamp was not remembered, all changes are fetched again.
+        let report = remote_settings.sync().await.unwrap();
+        assert_eq!(report.timestamp, 44);
+    }
+
+    #[tokio::test]
+    async fn test_backoff_is_persisted() {
+        init();
+
+        let folder = std::env::temp_dir().join("test_remote_settings_backoff_is_persisted");
+        let _ = std::fs::remove_dir_all(&folder);
+        let new_remote_settings = || {
+            let mut response = changeset_response(
+                format!(
+                    "{}/buckets/monitor/collections/changes/changeset?_expected=0",
+                    FAKE_SERVER
+                ),
+                json!({"metadata": {}, "changes": [], "timestamp": 42}),
+            );
+            response
+                .response_headers
+                .insert("backoff".to_string(), "300".to_string());
+            RemoteSettings::builder()
+                .server_url(FAKE_SERVER)
+                .http_client(Box::new(TestHttpClient::new(vec![response])))
+                .storage(Box::new(FileStorage {
+                    folder: folder.clone(),
+                    ..FileStorage::default()
+                }))
+                .build()
+                .unwrap()
+        };
+
+        new_remote_settings().sync().await.unwrap();
+
+        // After a restart, the manager is still backing off.
+        let err = new_remote_settings().sync().await.unwrap_err();
+        assert!(matches!(err, ClientError::BackoffError(_)));
+
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -2,30 +2,38 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+pub mod canonical_json;
+pub(crate) mod certificate_cache;
 pub mod dummy_verifier;
+pub mod revocation;
 
 #[cfg(feature = "ring_verifier")]
 pub mod ring_verifier;
 
-#[cfg(feature = "ring_verifier")]
+#[cfg(feature = "rustcrypto_verifier")]
+pub mod rustcrypto_verifier;
+
+#[cfg(any(feature = "ring_verifier", feature = "rustcrypto_verifier"))]
 pub mod x509;
 
 #[cfg(feature = "rc_crypto_verifier")]
 pub mod rc_crypto_verifier;
 
-use super::net::{Requester, Response};
+use super::net::{Requester, Response, ServerClock};
 use crate::client::Collection;
 use async_trait::async_trait;
 use log::debug;
 use serde_json::{json, Value};
+use std::convert::TryFrom;
 use thiserror::Error;
 use url::{ParseError as URLParseError, Url};
+use x509_parser::pem::Pem;
 
 #[cfg(not(test))]
 use std::time::{SystemTime, UNIX_EPOCH};
 
 #[cfg(not(test))]
-fn epoch_seconds() -> u64 {
+pub(crate) fn epoch_seconds() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap() // Time won't go backwards.
@@ -36,13 +44,57 @@ fn epoch_seconds() -> u64 {
 use mock_instant;
 
 #[cfg(test)]
-fn epoch_seconds() -> u64 {
+pub(crate) fn epoch_seconds() -> u64 {
     mock_instant::global::MockClock::time().as_secs()
 }
 
+/// Return the validity periods (`notBefore`, `notAfter`) of the certificates of a PEM chain,
+/// in seconds since epoch, or `None` if the chain cannot be parsed.
+pub(crate) fn chain_validity(pem_bytes: &[u8]) -> Option<Vec<(i64, i64)>> {
+    let mut periods = Vec::new();
+    for entry in Pem::iter_from_buffer(pem_bytes) {
+        let pem = entry.ok()?;
+        let cert = pem.parse_x509().ok()?;
+        let validity = &cert.tbs_certificate.validity;
+        periods.push((
+            validity.not_before.timestamp(),
+            validity.not_after.timestamp(),
+        ));
+    }
+    if periods.is_empty() {
+        return None;
+    }
+    Some(periods)
+}
+
+/// Return the period during which all the certificates of a PEM chain are valid,
+/// in seconds since epoch, or `None` if the chain cannot be parsed.
+pub(crate) fn validity_period(pem_bytes: &[u8]) -> Option<(u64, u64)> {
+    let periods = chain_validity(pem_bytes)?;
+    let not_before = periods.iter().map(|p| p.0).max()?.max(0) as u64;
+    let not_after = periods.iter().map(|p| p.1).min()?.max(0) as u64;
+    Some((not_before, not_after))
+}
+
+/// Verify that each certificate is valid at the server time, up to `clock.tolerance`
+/// seconds before and after its validity period.
+pub(crate) fn check_validity(
+    periods: &[(i64, i64)],
+    clock: &ServerClock,
+) -> Result<(), SignatureError> {
+    let now = i64::try_from(clock.now).unwrap_or(i64::MAX);
+    let tolerance = i64::try_from(clock.tolerance).unwrap_or(i64::MAX);
+    for (not_before, not_after) in periods {
+        if now < not_before.saturating_sub(tolerance) || now > not_after.saturating_add(tolerance) {
+            return Err(SignatureError::CertificateExpired);
+        }
+    }
+    Ok(())
+}
+
 /// A trait for signature verification of collection data.
 ///
-/// You may want to use your own verification implementation (eg. using OpenSSL instead of `ring` or `rc_crypto`).
+/// You may want to use your own verification implementation (eg. using OpenSSL instead of `ring`, RustCrypto or `rc_crypto`).
 ///
 /// # How can I implement ```Verification```?
 /// ```rust
@@ -101,7 +153,6 @@ pub trait Verification: Send + Sync {
             });
         }
 
-        // TODO: read server time from headers to compute clock skew and return along
         Ok(response.body)
     }
 
@@ -111,7 +162,7 @@ pub trait Verification: Send + Sync {
         let serialized = canonical_json::to_string(&json!({
             "data": sorted_records.into_iter().map(|r| r.value).collect::<Vec<Value>>(),
             "last_modified": collection.timestamp.to_string()
-        }))?;
+        }));
         let data = format!("Content-Signature:\x00{}", serialized);
         Ok(data.as_bytes().to_vec())
     }
@@ -141,9 +192,9 @@ pub trait Verification: Send + Sync {
 
         let data_bytes = self.serialize_data(collection)?;
 
-        let now = epoch_seconds();
-        self.verify_nist384p_chain(
-            now,
+        // Use the server time, since the local clock may be wrong.
+        self.verify_nist384p_chain_with_clock(
+            &requester.server_clock(),
             &pem_bytes,
             root_hash,
             &collection.signer,
@@ -155,9 +206,9 @@ pub trait Verification: Send + Sync {
     /// Verify chain of trust.
     /// 1. Parse the PEM bytes as DER-encoded X.509 Certificate.
     /// 2. Verify that root hash matches the SHA256 fingerprint of the root certificate (DER content)
-    /// 3. Verify that each certificate of the chain is currently valid (revocation support is optional)
+    /// 3. Verify that each certificate of the chain is currently valid (revocations are checked by the caller, see [`revocation::RevocationChecker`])
     /// 4. Verify that each child signature matches its parent's public key for each pair in the chain
-    /// 5. Verify that the subject alternate name of the end-entity certificate matches the collection signer name.
+    /// 5. Verify that the subject alternate name of the end-entity certificate matches the collection signer name, and that it is allowed to sign code.
     /// 6. Use the chain's end-entity (leaf) certificate to verify that the "signature" property matches the contents of the data.
     fn verify_nist384p_chain(
         &self,
@@ -169,7 +220,76 @@ pub trait Verification: Send + Sync {
         signature: &[u8],
     ) -> Result<(), SignatureError>;
 
+    /// Verify chain of trust like [`Verification::verify_nist384p_chain`], with the clock
+    /// of the server: certificates are considered valid up to `clock.tolerance` seconds
+    /// before and after their validity period.
+    ///
+    /// By default, if a certificate is not valid at the server time but each one is within
+    /// the tolerance (like with `RingVerifier`), the chain is verified with
+    /// [`Verification::verify_nist384p_chain`] at the closest time when all of them are valid.
+    /// If there is no such time, or if the chain cannot be parsed, the server time is used.
+    fn verify_nist384p_chain_with_clock(
+        &self,
+        clock: &ServerClock,
+        pem_bytes: &[u8],
+        root_hash: &str,
+        subject_cn: &str,
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<(), SignatureError> {
+        let mut now = clock.now;
+        let exact = ServerClock {
+            tolerance: 0,
+            ..*clock
+        };
+        match chain_validity(pem_bytes) {
+            Some(periods)
+                if check_validity(&periods, &exact).is_err()
+                    && check_validity(&periods, clock).is_ok() =>
+            {
+                if let Some((not_before, not_after)) = validity_period(pem_bytes) {
+                    if not_before <= not_after {
+                        debug!("Certificate not valid, but within clock skew tolerance.");
+                        now = now.clamp(not_before, not_after);
+                    }
+                }
+            }
+            _ => (),
+        }
+        self.verify_nist384p_chain(now, pem_bytes, root_hash, subject_cn, message, signature)
+    }
+
     fn verify_sha256_hash(&self, content: &[u8], expected: &[u8]) -> Result<(), SignatureError>;
+
+    /// Whether this verifier actually checks signatures.
+    ///
+    /// Verifiers that accept any content, like [`DummyVerifier`](dummy_verifier::DummyVerifier),
+    /// return `false`, and are refused with [`VerificationMode::Strict`].
+    fn checks_signatures(&self) -> bool {
+        true
+    }
+
+    /// Verify that a SHA256 digest, computed while receiving some content, matches the expected hash.
+    fn verify_sha256_digest(&self, digest: &[u8], expected: &[u8]) -> Result<(), SignatureError> {
+        if digest == expected {
+            Ok(())
+        } else {
+            Err(SignatureError::MismatchError(
+                "content did not match expected sha256 hash".to_string(),
+            ))
+        }
+    }
+}
+
+/// Whether a [`Client`](crate::Client) may run without verifying signatures.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum VerificationMode {
+    /// Building the client fails unless a verifier that checks signatures is configured.
+    Strict,
+    /// Any verifier is accepted, but a warning is logged on every synchronization
+    /// when signatures are not checked.
+    #[default]
+    Insecure,
 }
 
 #[derive(Debug, Error)]
@@ -188,6 +308,10 @@ pub enum SignatureError {
     InvalidCertificateSubject(String),
     #[error("certificate expired")]
     CertificateExpired,
+    #[error("certificate was revoked: {0}")]
+    CertificateRevoked(String),
+    #[error("certificate is not allowed to sign content")]
+    InvalidCertificateUsage,
     #[error("certificate chain could not be verified")]
     CertificateTrustError,
     #[error("certificate chain was signed with unsupported algorithm")]
@@ -202,8 +326,6 @@ pub enum SignatureError {
     HTTPBackendError(),
     #[error("bad URL format: {0}")]
     URLError(#[from] URLParseError),
-    #[error("data could not be serialized: {0}")]
-    SerializationError(#[from] canonical_json::CanonicalJSONError),
 }
 
 #[cfg(test)]
@@ -215,7 +337,8 @@ mod tests {
     use env_logger;
     use httpmock::MockServer;
     use mock_instant::global::MockClock;
-    use serde_json::json;
+    use serde::Deserialize;
+    use serde_json::{json, Value};
     use std::time::Duration;
 
     impl PartialEq for SignatureError {
@@ -245,7 +368,12 @@ mod tests {
         let mut verifiers: Vec<Box<dyn Verification>> = Vec::new();
 
         #[cfg(feature = "ring_verifier")]
-        verifiers.push(Box::new(super::ring_verifier::RingVerifier {}));
+        verifiers.push(Box::new(super::ring_verifier::RingVerifier::new()));
+
+        #[cfg(feature = "rustcrypto_verifier")]
+        verifiers.push(Box::new(
+            super::rustcrypto_verifier::RustCryptoVerifier::new(),
+        ));
 
         #[cfg(feature = "rc_crypto_verifier")]
         verifiers.push(Box::new(super::rc_crypto_verifier::RcCryptoVerifier {}));
@@ -666,4 +794,152 @@ HszKVANqXQIxAIygMaeTiD9figEusmHMthBdFoIoHk31x4MHukAy+TWZ863X6/V2
             Err(SignatureError::CertificateExpired),
         ).await;
     }
+
+    /// A signed changeset, along with its certificate chain. Generated entries are
+    /// updated by `signatures/fixtures/generate.mjs`.
+    #[derive(Deserialize)]
+    struct SignedCollection {
+        description: String,
+        /// Where the changeset and its chain come from.
+        source: String,
+        bid: String,
+        cid: String,
+        signer: String,
+        root_hash: String,
+        /// Time at which the certificate chain is valid, in seconds since epoch.
+        now: u64,
+        changeset: Value,
+        chain: String,
+    }
+
+    #[test]
+    #[allow(clippy::vec_init_then_push)]
+    fn test_signed_collections() {
+        use crate::client::net::ServerClock;
+
+        #[allow(unused_mut, clippy::vec_init_then_push)]
+        let mut verifiers: Vec<Box<dyn Verification>> = Vec::new();
+
+        #[cfg(feature = "ring_verifier")]
+        verifiers.push(Box::new(super::ring_verifier::RingVerifier::new()));
+
+        #[cfg(feature = "rustcrypto_verifier")]
+        verifiers.push(Box::new(
+            super::rustcrypto_verifier::RustCryptoVerifier::new(),
+        ));
+
+        #[cfg(feature = "rc_crypto_verifier")]
+        verifiers.push(Box::new(super::rc_crypto_verifier::RcCryptoVerifier {}));
+
+        let fixtures: Vec<SignedCollection> =
+            serde_json::from_str(include_str!("signatures/fixtures/signed_collections.json"))
+                .unwrap();
+        for fixture in fixtures {
+            let mut collection = Collection {
+                bid: fixture.bid.clone(),
+                cid: fixture.cid.clone(),
+                metadata: fixture.changeset["metadata"].clone(),
+                records: fixture.changeset["changes"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .cloned()
+                    .map(Record::new)
+                    .collect(),
+                timestamp: fixture.changeset["timestamp"].as_u64().unwrap(),
+                signer: fixture.signer.clone(),
+            };
+            let signature = collection.metadata["signature"]["signature"]
+                .as_str()
+                .unwrap()
+                .to_owned();
+            let clock = ServerClock {
+                now: fixture.now,
+                tolerance: 0,
+            };
+
+            for verifier in &verifiers {
+                let verify = |collection: &Collection| {
+                    verifier.verify_nist384p_chain_with_clock(
+                        &clock,
+                        fixture.chain.as_bytes(),
+                        &fixture.root_hash,
+                        &collection.signer,
+                        &verifier.serialize_data(collection).unwrap(),
+                        signature.as_bytes(),
+                    )
+                };
+                if let Err(err) = verify(&collection) {
+                    panic!("{} ({}): {}", fixture.description, fixture.source, err);
+                }
+
+                // The timestamp is signed too.
+                collection.timestamp += 1;
+                assert_eq!(
+                    verify(&collection),
+                    Err(SignatureError::MismatchError("".to_string())),
+                    "{}",
+                    fixture.description
+                );
+                collection.timestamp -= 1;
+            }
+        }
+    }
+
+    #[test]
+    fn test_default_clock_skew_tolerance() {
+        use super::certificate_cache::tests::CERTIFICATE;
+        use crate::client::net::ServerClock;
+        use std::sync::Mutex;
+
+        // Valid until 2099-12-31.
+        const NOT_AFTER: u64 = 4102358400;
+
+        /// Check the validity of the chain only, at the time it is given.
+        #[derive(Default)]
+        struct ClockVerifier {
+            now: Mutex<u64>,
+        }
+
+        impl Verification for ClockVerifier {
+            fn verify_nist384p_chain(
+                &self,
+                epoch_seconds: u64,
+                _pem_bytes: &[u8],
+                _root_hash: &str,
+                _subject_cn: &str,
+                _message: &[u8],
+                _signature: &[u8],
+            ) -> Result<(), SignatureError> {
+                *self.now.lock().unwrap() = epoch_seconds;
+                if epoch_seconds > NOT_AFTER {
+                    return Err(SignatureError::CertificateExpired);
+                }
+                Ok(())
+            }
+
+            fn verify_sha256_hash(&self, _: &[u8], _: &[u8]) -> Result<(), SignatureError> {
+                Ok(())
+            }
+        }
+
+        let verifier = ClockVerifier::default();
+        let verify_at = |now, tolerance| {
+            let result = verifier.verify_nist384p_chain_with_clock(
+                &ServerClock { now, tolerance },
+                CERTIFICATE.as_bytes(),
+                "",
+                "",
+                b"",
+                b"",
+            );
+            (result.is_ok(), *verifier.now.lock().unwrap())
+        };
+
+        assert_eq!(verify_at(1000, 300), (true, 1000));
+        assert_eq!(verify_at(NOT_AFTER + 60, 0), (false, NOT_AFTER + 60));
+        // Within the tolerance, the chain is verified when its certificates are valid.
+        assert_eq!(verify_at(NOT_AFTER + 60, 300), (true, NOT_AFTER));
+        assert_eq!(verify_at(NOT_AFTER + 600, 300), (false, NOT_AFTER + 600));
+    }
 }
@@ -0,0 +1,155 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Canonical JSON serialization, as performed by Firefox (`CanonicalJSON.sys.mjs`)
+//! to verify content signatures.
+//!
+//! * Object keys are sorted by UTF-16 code units, like `Array.prototype.sort()`;
+//! * Strings are escaped like `jsesc` with the `json` and `lowercaseHex` options: every
+//!   character outside of printable ASCII is escaped as `\uxxxx`;
+//! * Numbers are formatted like `Number.prototype.toString()`.
+
+use serde_json::{Number, Value};
+
+/// Largest integer that a JavaScript number represents exactly (`Number.MAX_SAFE_INTEGER`).
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+/// Serialize the value in canonical JSON.
+pub fn to_string(value: &Value) -> String {
+    let mut out = String::new();
+    write_value(&mut out, value);
+    out
+}
+
+fn write_value(out: &mut String, value: &Value) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(n) => write_number(out, n),
+        Value::String(s) => write_string(out, s),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_value(out, item);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+            out.push('{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_string(out, key);
+                out.push(':');
+                write_value(out, value);
+            }
+            out.push('}');
+        }
+    }
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for unit in s.encode_utf16() {
+        match unit {
+            0x22 => out.push_str("\\\""),
+            0x5c => out.push_str("\\\\"),
+            0x08 => out.push_str("\\b"),
+            0x0c => out.push_str("\\f"),
+            0x0a => out.push_str("\\n"),
+            0x0d => out.push_str("\\r"),
+            0x09 => out.push_str("\\t"),
+            // Printable ASCII.
+            0x20..=0x7e => out.push(unit as u8 as char),
+            // Everything else, including each half of surrogate pairs.
+            _ => out.push_str(&format!("\\u{:04x}", unit)),
+        }
+    }
+    out.push('"');
+}
+
+fn write_number(out: &mut String, number: &Number) {
+    if let Some(n) = number.as_u64().filter(|n| *n <= MAX_SAFE_INTEGER) {
+        out.push_str(&n.to_string());
+    } else if let Some(n) = number
+        .as_i64()
+        .filter(|n| n.unsigned_abs() <= MAX_SAFE_INTEGER)
+    {
+        out.push_str(&n.to_string());
+    } else {
+        // Larger integers are parsed as floats by JavaScript.
+        write_float(out, number.as_f64().unwrap_or_default());
+    }
+}
+
+/// Format a float like `Number.prototype.toString()` (ECMAScript `Number::toString`).
+fn write_float(out: &mut String, f: f64) {
+    if f == 0.0 {
+        out.push_str(if f.is_sign_negative() { "-0" } else { "0" });
+        return;
+    }
+    if f < 0.0 {
+        out.push('-');
+    }
+    // The shortest digits that represent the float, and its exponent: `d.ddde±x`.
+    let scientific = format!("{:e}", f.abs());
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let digits = mantissa.replace('.', "");
+    let k = digits.len() as i32;
+    // Position of the decimal point relative to the digits.
+    let n = exponent.parse::<i32>().unwrap() + 1;
+
+    if k <= n && n <= 21 {
+        out.push_str(&digits);
+        out.push_str(&"0".repeat((n - k) as usize));
+    } else if 0 < n && n <= 21 {
+        out.push_str(&digits[..n as usize]);
+        out.push('.');
+        out.push_str(&digits[n as usize..]);
+    } else if -6 < n && n <= 0 {
+        out.push_str("0.");
+        out.push_str(&"0".repeat(-n as usize));
+        out.push_str(&digits);
+    } else {
+        out.push_str(&digits[..1]);
+        if k > 1 {
+            out.push('.');
+            out.push_str(&digits[1..]);
+        }
+        out.push('e');
+        out.push(if n > 0 { '+' } else { '-' });
+        out.push_str(&(n - 1).abs().to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_string;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    struct Case {
+        description: String,
+        input: String,
+        expected: String,
+    }
+
+    /// Inputs like the records of signed collections. The expected outputs are generated
+    /// by `fixtures/generate.mjs`, with a copy of Firefox's `CanonicalJSON.stringify()`.
+    #[test]
+    fn test_firefox_conformance() {
+        let cases: Vec<Case> =
+            serde_json::from_str(include_str!("fixtures/canonical_json.json")).unwrap();
+        for case in cases {
+            let value: serde_json::Value = serde_json::from_str(&case.input).unwrap();
+            assert_eq!(to_string(&value), case.expected, "{}", case.description);
+        }
+    }
+}
@@ -0,0 +1,394 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use super::{check_validity, validity_period};
+use crate::client::net::{
+    Headers, Method, Requester, Response, ServerClock, StreamingResponse, Url,
+};
+use crate::client::storage::{Storage, StorageError};
+use async_trait::async_trait;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+/// Storage key of the certificate chains.
+const STORAGE_KEY: &str = "certificates";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedChain {
+    pem: Vec<u8>,
+    not_before: u64,
+    not_after: u64,
+}
+
+impl CachedChain {
+    /// Parse a PEM certificate chain, and compute the period during which all its
+    /// certificates are valid.
+    fn parse(pem: Vec<u8>) -> Option<Self> {
+        let (not_before, not_after) = validity_period(&pem)?;
+        Some(CachedChain {
+            pem,
+            not_before,
+            not_after,
+        })
+    }
+
+    /// Whether the chain is valid at the specified time, like when verifying signatures.
+    fn is_valid_at(&self, clock: &ServerClock) -> bool {
+        let period = (self.not_before as i64, self.not_after as i64);
+        check_validity(&[period], clock).is_ok()
+    }
+
+    /// Whether the chain has expired at the specified time, like when verifying signatures.
+    fn has_expired_at(&self, clock: &ServerClock) -> bool {
+        clock.now > self.not_after.saturating_add(clock.tolerance)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Chains {
+    by_url: HashMap<String, CachedChain>,
+    // URLs of the chains used to verify signatures.
+    x5u: HashSet<String>,
+    modified: bool,
+}
+
+impl Chains {
+    /// Add chains, keeping the one valid the longest when a URL is already known.
+    fn merge(&mut self, other: HashMap<String, CachedChain>) {
+        for (url, chain) in other {
+            match self.by_url.get(&url) {
+                Some(known) if known.not_after >= chain.not_after => (),
+                _ => {
+                    self.by_url.insert(url, chain);
+                }
+            }
+        }
+    }
+}
+
+/// Read the chains from the storage, which may be shared with other clients.
+fn read_stored(storage: &dyn Storage) -> HashMap<String, CachedChain> {
+    storage
+        .retrieve(STORAGE_KEY)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default()
+}
+
+/// Certificate chains downloaded to verify signatures, keyed by `x5u` URL.
+///
+/// Chains are kept until one of their certificates expires. Only the URLs
+/// declared with [`CertificateCache::expect`] are cached.
+#[derive(Default)]
+pub(crate) struct CertificateCache {
+    chains: Mutex<Chains>,
+}
+
+impl std::fmt::Debug for CertificateCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CertificateCache").finish_non_exhaustive()
+    }
+}
+
+impl CertificateCache {
+    fn get(&self, url: &str, clock: &ServerClock) -> Option<Vec<u8>> {
+        let chains = self.chains.lock().unwrap();
+        match chains.by_url.get(url) {
+            Some(chain) if chain.is_valid_at(clock) => Some(chain.pem.clone()),
+            _ => None,
+        }
+    }
+
+    fn insert(&self, url: &str, pem: Vec<u8>) {
+        let mut chains = self.chains.lock().unwrap();
+        if !chains.x5u.contains(url) {
+            return;
+        }
+        if let Some(chain) = CachedChain::parse(pem) {
+            chains.by_url.insert(url.to_string(), chain);
+            chains.modified = true;
+        }
+    }
+
+    /// Declare the URL of a certificate chain (`x5u`) that will be downloaded to verify
+    /// a signature.
+    pub fn expect(&self, url: &str) {
+        self.chains.lock().unwrap().x5u.insert(url.to_string());
+    }
+
+    /// Read the chains from the storage, including those stored by other clients since
+    /// the last time.
+    pub fn load(&self, storage: &dyn Storage) {
+        let stored = read_stored(storage);
+        self.chains.lock().unwrap().merge(stored);
+    }
+
+    /// Write the chains to the storage if some were downloaded, and remove those expired
+    /// at the time of the server.
+    ///
+    /// The chains stored by other clients in the meantime are kept.
+    pub fn save(&self, storage: &mut dyn Storage, clock: &ServerClock) -> Result<(), StorageError> {
+        let mut chains = self.chains.lock().unwrap();
+        if !chains.modified {
+            return Ok(());
+        }
+        chains.merge(read_stored(storage));
+        chains
+            .by_url
+            .retain(|_, chain| !chain.has_expired_at(clock));
+        let bytes = serde_json::to_vec(&chains.by_url).map_err(|err| {
+            StorageError::WriteError(format!("cannot serialize certificates: {}", err))
+        })?;
+        storage.store(STORAGE_KEY, bytes)?;
+        chains.modified = false;
+        Ok(())
+    }
+}
+
+/// An HTTP client that serves the certificate chains from a [`CertificateCache`],
+/// and only downloads them with another client when missing or expired.
+///
+/// The validity of the chains is checked with the clock of the other client, like
+/// when verifying signatures.
+///
+/// The chains are requested by [`Verification::fetch_certificate_chain`](super::Verification::fetch_certificate_chain)
+/// with [`Requester::get`], and the URLs to cache are declared by the client before
+/// verifying signatures.
+#[derive(Debug)]
+pub(crate) struct CertificateClient {
+    inner: Box<dyn Requester + 'static>,
+    cache: Arc<CertificateCache>,
+}
+
+impl CertificateClient {
+    pub fn new(inner: Box<dyn Requester + 'static>, cache: Arc<CertificateCache>) -> Self {
+        Self { inner, cache }
+    }
+}
+
+#[async_trait]
+impl Requester for CertificateClient {
+    async fn get(&self, url: Url) -> Result<Response, ()> {
+        if let Some(pem) = self.cache.get(url.as_str(), &self.inner.server_clock()) {
+            debug!("Use cached certificate chain {}", url);
+            return Ok(Response {
+                status: 200,
+                body: pem,
+                headers: Headers::new(),
+            });
+        }
+        let response = self.inner.get(url.clone()).await?;
+        if response.is_success() {
+            self.cache.insert(url.as_str(), response.body.clone());
+        }
+        Ok(response)
+    }
+
+    async fn get_with_headers(&self, url: Url, headers: Headers) -> Result<Response, ()> {
+        self.inner.get_with_headers(url, headers).await
+    }
+
+    async fn get_streaming(&self, url: Url) -> Result<StreamingResponse, ()> {
+        self.inner.get_streaming(url).await
+    }
+
+    async fn request_json(
+        &self,
+        method: Method,
+        url: Url,
+        data: Vec<u8>,
+        headers: Headers,
+    ) -> Result<Response, ()> {
+        self.inner.request_json(method, url, data, headers).await
+    }
+
+    fn server_clock(&self) -> ServerClock {
+        self.inner.server_clock()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{CachedChain, CertificateCache, CertificateClient};
+    use crate::client::net::{Headers, Method, Requester, Response, ServerClock};
+    use crate::client::storage::memory_storage::MemoryStorage;
+    use async_trait::async_trait;
+    use std::sync::{Arc, Mutex};
+
+    // Valid from 1970-01-01 to 2099-12-31.
+    pub const CERTIFICATE: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBdjCCARugAwIBAgIUYi5z1RC/2EIIq5yJu3r/axLB0UYwCgYIKoZIzj0EAwIw
+DzENMAsGA1UEAwwEdGVzdDAgFw03MDAxMDEwMDAwMDBaGA8yMDk5MTIzMTAwMDAw
+MFowDzENMAsGA1UEAwwEdGVzdDBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABLgs
+UxwbrDlo+H2s9QLJoOgApyiglvmYIUxE0qEIgcXBBj5+dWoijLjSodrgy3D0qW7L
+GdXk/Uqj7j/nrdcOwm6jUzBRMB0GA1UdDgQWBBSF+x0yO3jBQ12yWEiwJ6S/LIjm
+yDAfBgNVHSMEGDAWgBSF+x0yO3jBQ12yWEiwJ6S/LIjmyDAPBgNVHRMBAf8EBTAD
+AQH/MAoGCCqGSM49BAMCA0kAMEYCIQDAc1dIV089HPEWjMXq1ENtw55oO0bagyEn
+cbyEfJ2GwAIhAO/oPQ5iLPnZi1+59MqoG1nJwwngPr19U001d2RYsHGB
+-----END CERTIFICATE-----";
+    const NOT_AFTER: u64 = 4102358400;
+
+    const fn at(now: u64, tolerance: u64) -> ServerClock {
+        ServerClock { now, tolerance }
+    }
+
+    /// Respond with the certificate, and count the requests.
+    #[derive(Debug, Clone, Default)]
+    struct CertificateServer {
+        requests: Arc<Mutex<u32>>,
+        now: Arc<Mutex<u64>>,
+        tolerance: u64,
+    }
+
+    #[async_trait]
+    impl Requester for CertificateServer {
+        async fn get(&self, _url: url::Url) -> Result<Response, ()> {
+            *self.requests.lock().unwrap() += 1;
+            Ok(Response {
+                status: 200,
+                body: CERTIFICATE.as_bytes().to_vec(),
+                headers: Headers::new(),
+            })
+        }
+
+        async fn request_json(
+            &self,
+            _method: Method,
+            _url: url::Url,
+            _data: Vec<u8>,
+            _headers: Headers,
+        ) -> Result<Response, ()> {
+            unreachable!()
+        }
+
+        fn server_clock(&self) -> ServerClock {
+            at(*self.now.lock().unwrap(), self.tolerance)
+        }
+    }
+
+    #[test]
+    fn test_parse_validity() {
+        let chain = CachedChain::parse(CERTIFICATE.as_bytes().to_vec()).unwrap();
+        assert_eq!(chain.not_before, 0);
+        assert_eq!(chain.not_after, NOT_AFTER);
+
+        assert!(CachedChain::parse(b"not a certificate".to_vec()).is_none());
+        assert!(CachedChain::parse(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn test_expired_chains_are_ignored() {
+        let cache = CertificateCache::default();
+        cache.expect("https://x5u");
+        cache.expect("https://invalid");
+        cache.insert("https://x5u", CERTIFICATE.as_bytes().to_vec());
+        cache.insert("https://invalid", b"invalid".to_vec());
+
+        assert!(cache.get("https://x5u", &at(NOT_AFTER, 0)).is_some());
+        assert!(cache.get("https://x5u", &at(NOT_AFTER + 1, 0)).is_none());
+        assert!(cache.get("https://invalid", &at(0, 0)).is_none());
+
+        // Like signatures, chains are valid within the clock skew tolerance.
+        assert!(cache.get("https://x5u", &at(NOT_AFTER + 60, 60)).is_some());
+        assert!(cache.get("https://x5u", &at(NOT_AFTER + 61, 60)).is_none());
+
+        let mut storage = MemoryStorage::new();
+        cache.save(&mut storage, &at(NOT_AFTER + 60, 60)).unwrap();
+        let restored = CertificateCache::default();
+        restored.load(&storage);
+        assert!(restored.get("https://x5u", &at(0, 0)).is_some());
+
+        cache.insert("https://x5u", CERTIFICATE.as_bytes().to_vec());
+        cache.save(&mut storage, &at(NOT_AFTER + 61, 60)).unwrap();
+        let restored = CertificateCache::default();
+        restored.load(&storage);
+        assert!(restored.get("https://x5u", &at(0, 0)).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_serves_cached_chains() {
+        let server = CertificateServer::default();
+        let cache = Arc::new(CertificateCache::default());
+        let client = CertificateClient::new(Box::new(server.clone()), cache.clone());
+        let url: url::Url = "https://example.com/chain.pem".parse().unwrap();
+        cache.expect(url.as_str());
+
+        for _ in 0..2 {
+            let response = client.get(url.clone()).await.unwrap();
+            assert_eq!(response.body, CERTIFICATE.as_bytes());
+        }
+        assert_eq!(*server.requests.lock().unwrap(), 1);
+
+        // Other requests are not cached.
+        let other: url::Url = "https://example.com/other.pem".parse().unwrap();
+        client.get(other.clone()).await.unwrap();
+        client.get(other).await.unwrap();
+        assert_eq!(*server.requests.lock().unwrap(), 3);
+
+        // Chains are restored from storage.
+        let mut storage = MemoryStorage::new();
+        cache.save(&mut storage, &at(0, 0)).unwrap();
+        let restored = Arc::new(CertificateCache::default());
+        restored.load(&storage);
+        let client = CertificateClient::new(Box::new(server.clone()), restored);
+        client.get(url.clone()).await.unwrap();
+        assert_eq!(*server.requests.lock().unwrap(), 3);
+
+        // Chains expired at the time of the server are downloaded again.
+        *server.now.lock().unwrap() = NOT_AFTER + 1;
+        client.get(url).await.unwrap();
+        assert_eq!(*server.requests.lock().unwrap(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_uses_server_clock_tolerance() {
+        let server = CertificateServer {
+            tolerance: 60,
+            ..CertificateServer::default()
+        };
+        let cache = Arc::new(CertificateCache::default());
+        let client = CertificateClient::new(Box::new(server.clone()), cache.clone());
+        let url: url::Url = "https://example.com/chain.pem".parse().unwrap();
+        cache.expect(url.as_str());
+        client.get(url.clone()).await.unwrap();
+
+        // The chain is still accepted by verifiers.
+        *server.now.lock().unwrap() = NOT_AFTER + 60;
+        client.get(url.clone()).await.unwrap();
+        assert_eq!(*server.requests.lock().unwrap(), 1);
+        assert_eq!(client.server_clock(), at(NOT_AFTER + 60, 60));
+
+        *server.now.lock().unwrap() = NOT_AFTER + 61;
+        client.get(url).await.unwrap();
+        assert_eq!(*server.requests.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_shared_storage() {
+        let mut storage = MemoryStorage::new();
+        let (first, second) = (CertificateCache::default(), CertificateCache::default());
+        first.load(&storage);
+        second.load(&storage);
+
+        first.expect("https://first");
+        first.insert("https://first", CERTIFICATE.as_bytes().to_vec());
+        first.save(&mut storage, &at(0, 0)).unwrap();
+        second.expect("https://second");
+        second.insert("https://second", CERTIFICATE.as_bytes().to_vec());
+        second.save(&mut storage, &at(0, 0)).unwrap();
+
+        // The chains of both caches are stored.
+        let restored = CertificateCache::default();
+        restored.load(&storage);
+        assert!(restored.get("https://first", &at(0, 0)).is_some());
+        assert!(restored.get("https://second", &at(0, 0)).is_some());
+
+        // Chains stored by another cache are loaded again.
+        first.load(&storage);
+        assert!(first.get("https://second", &at(0, 0)).is_some());
+    }
+}
@@ -37,4 +37,13 @@ impl Verification for DummyVerifier {
         debug!("default verifier implementation");
         Ok(())
     }
+
+    fn verify_sha256_digest(&self, _digest: &[u8], _expected: &[u8]) -> Result<(), SignatureError> {
+        debug!("default verifier implementation");
+        Ok(())
+    }
+
+    fn checks_signatures(&self) -> bool {
+        false
+    }
 }
@@ -0,0 +1,171 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+// Generate the expected outputs of `canonical_json.json`, and the generated entries of
+// `signed_collections.json`, with Firefox's canonical JSON serialization.
+//
+//     node src/client/signatures/fixtures/generate.mjs
+//
+// Requires Node.js and OpenSSL 3.4+ (to create the test certificates).
+
+import { execFileSync } from "node:child_process";
+import { createHash, sign } from "node:crypto";
+import { mkdtempSync, readFileSync, writeFileSync } from "node:fs";
+import { tmpdir } from "node:os";
+import { dirname, join } from "node:path";
+import { fileURLToPath } from "node:url";
+
+const FIXTURES = dirname(fileURLToPath(import.meta.url));
+
+// The subset of `jsesc(value, { lowercaseHex: true, json: true })` used by
+// `CanonicalJSON.stringify()`, from toolkit/modules/third_party/jsesc/jsesc.mjs:
+// printable ASCII is kept, `"`, `\` and `\b\f\n\r\t` have short escapes, and
+// any other UTF-16 code unit is escaped as `\uXXXX`.
+const SINGLE_ESCAPES = {
+  '"': '\\"',
+  "\\": "\\\\",
+  "\b": "\\b",
+  "\f": "\\f",
+  "\n": "\\n",
+  "\r": "\\r",
+  "\t": "\\t",
+};
+
+function jsesc(value) {
+  if (typeof value !== "string") {
+    // Numbers, booleans and `null`.
+    return JSON.stringify(value) || "null";
+  }
+  let result = "";
+  for (let i = 0; i < value.length; i++) {
+    const character = value.charAt(i);
+    if (/[ !#-&'(-\[\]-~]/.test(character)) {
+      result += character;
+    } else if (character in SINGLE_ESCAPES) {
+      result += SINGLE_ESCAPES[character];
+    } else {
+      result += "\\u" + ("0000" + character.charCodeAt(0).toString(16)).slice(-4);
+    }
+  }
+  return `"${result}"`;
+}
+
+// `CanonicalJSON.stringify()` from toolkit/modules/CanonicalJSON.sys.mjs.
+function stringify(source) {
+  if (Array.isArray(source)) {
+    const jsonArray = source.map(x => (typeof x === "undefined" ? null : x));
+    return "[" + jsonArray.map(item => stringify(item)).join(",") + "]";
+  }
+
+  if (typeof source === "number") {
+    if (source === 0) {
+      return Object.is(source, -0) ? "-0" : "0";
+    }
+  }
+
+  if (typeof source !== "object" || source === null) {
+    return jsesc(source);
+  }
+
+  const sortedKeys = Object.keys(source).sort();
+  const lastIndex = sortedKeys.length - 1;
+  return (
+    sortedKeys.reduce((serial, key, index) => {
+      const value = source[key];
+      if (typeof value === "undefined") {
+        return serial;
+      }
+      const jsonValue = value && value.toJSON ? value.toJSON() : value;
+      const suffix = index !== lastIndex ? "," : "";
+      return serial + jsesc(key) + ":" + stringify(jsonValue) + suffix;
+    }, "{") + "}"
+  );
+}
+
+// The signed content of a collection, like `RemoteSettingsWorker.canonicalStringify()`.
+function signedContent(records, timestamp) {
+  const sorted = [...records].sort((a, b) => (a.id < b.id ? -1 : a.id > b.id ? 1 : 0));
+  return "Content-Signature:\x00" + stringify({ data: sorted, last_modified: `${timestamp}` });
+}
+
+function updateCanonicalJSON() {
+  const path = join(FIXTURES, "canonical_json.json");
+  const cases = JSON.parse(readFileSync(path, "utf8"));
+  for (const testCase of cases) {
+    testCase.expected = stringify(JSON.parse(testCase.input));
+  }
+  writeFileSync(path, JSON.stringify(cases, null, 2) + "\n");
+}
+
+// A root, an intermediate and an end-entity certificate for the signer, like the
+// chains of Autograph.
+function createChain(folder, signer) {
+  const openssl = (...args) => execFileSync("openssl", args, { cwd: folder, stdio: "pipe" });
+  const validity = ["-not_before", "20260101000000Z", "-not_after", "20460101000000Z"];
+  const extensions = {
+    "intermediate.ext":
+      "basicConstraints=critical,CA:TRUE\nkeyUsage=critical,keyCertSign,cRLSign\n" +
+      "extendedKeyUsage=codeSigning\n",
+    "ee.ext":
+      "keyUsage=critical,digitalSignature\nextendedKeyUsage=codeSigning\n" +
+      `subjectAltName=DNS:${signer}\n`,
+  };
+  for (const [name, content] of Object.entries(extensions)) {
+    writeFileSync(join(folder, name), content);
+  }
+  for (const name of ["root", "intermediate", "ee"]) {
+    openssl("ecparam", "-name", "secp384r1", "-genkey", "-noout", "-out", `${name}.key`);
+  }
+  openssl(
+    "req", "-x509", "-new", "-key", "root.key", "-subj", "/CN=Test Content Signing Root",
+    "-sha384", ...validity,
+    "-addext", "basicConstraints=critical,CA:TRUE",
+    "-addext", "keyUsage=critical,keyCertSign,cRLSign", "-out", "root.pem"
+  );
+  for (const [name, issuer, subject] of [
+    ["intermediate", "root", "/CN=Test Content Signing Intermediate"],
+    ["ee", "intermediate", `/CN=${signer}`],
+  ]) {
+    openssl("req", "-new", "-key", `${name}.key`, "-subj", subject, "-out", `${name}.csr`);
+    openssl(
+      "x509", "-req", "-in", `${name}.csr`, "-CA", `${issuer}.pem`, "-CAkey", `${issuer}.key`,
+      "-sha384", ...validity, "-set_serial", "1", "-extfile", `${name}.ext`, "-out", `${name}.pem`
+    );
+  }
+  const pem = name => readFileSync(join(folder, `${name}.pem`), "utf8");
+  const rootDer = openssl("x509", "-in", "root.pem", "-outform", "DER");
+  const rootHash = createHash("sha256").update(rootDer).digest("hex").toUpperCase();
+  return {
+    chain: pem("ee") + pem("intermediate") + pem("root"),
+    rootHash: rootHash.match(/../g).join(":"),
+    key: readFileSync(join(folder, "ee.key"), "utf8"),
+  };
+}
+
+// The records are kept as written in the fixture, since serializing them again would
+// change how their numbers are written: only the signatures and chains are replaced.
+function updateSignedCollections() {
+  const path = join(FIXTURES, "signed_collections.json");
+  let text = readFileSync(path, "utf8");
+  for (const fixture of JSON.parse(text).filter(f => f.source.startsWith("Generated"))) {
+    const folder = mkdtempSync(join(tmpdir(), "signed-collections-"));
+    const { chain, rootHash, key } = createChain(folder, fixture.signer);
+    const { changes, timestamp, metadata } = fixture.changeset;
+    const signature = sign("sha384", Buffer.from(signedContent(changes, timestamp)), {
+      key,
+      dsaEncoding: "ieee-p1363",
+    });
+    for (const [previous, value] of [
+      [metadata.signature.signature, signature.toString("base64url")],
+      [fixture.root_hash, rootHash],
+      [fixture.chain, chain],
+    ]) {
+      text = text.replace(JSON.stringify(previous), JSON.stringify(value));
+    }
+  }
+  writeFileSync(path, text);
+}
+
+updateCanonicalJSON();
+updateSignedCollections();
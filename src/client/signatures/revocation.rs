@@ -0,0 +1,332 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use super::{SignatureError, Verification};
+use crate::client::net::{Requester, ServerClock};
+use crate::client::{Collection, Record};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use log::warn;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+use x509_parser::pem::Pem;
+
+/// Identifiers of a certificate of the chain, as DER bytes.
+#[derive(Debug)]
+pub struct CertificateInfo<'a> {
+    /// The issuer distinguished name.
+    pub issuer: &'a [u8],
+    /// The serial number, without its DER tag and length.
+    pub serial_number: &'a [u8],
+    /// The subject distinguished name.
+    pub subject: &'a [u8],
+    /// The subject public key info.
+    pub subject_public_key_info: &'a [u8],
+}
+
+/// A trait to check whether the certificates of the chain (`x5u`) were revoked.
+///
+/// See [`ClientBuilder::revocation_checker`](crate::client::ClientBuilder::revocation_checker).
+pub trait RevocationChecker: std::fmt::Debug + Send + Sync {
+    /// Return `true` if the certificate was revoked.
+    fn is_revoked(&self, certificate: &CertificateInfo) -> bool;
+}
+
+/// A revocation entry of the `security-state/onecrl` collection.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OneCrlEntry {
+    issuer_name: Option<String>,
+    serial_number: Option<String>,
+    subject: Option<String>,
+    pub_key_hash: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct Revocations {
+    // (issuer, serial number)
+    by_issuer: HashSet<(Vec<u8>, Vec<u8>)>,
+    // (subject, SHA256 of the subject public key info)
+    by_subject: HashSet<(Vec<u8>, Vec<u8>)>,
+}
+
+/// A [`RevocationChecker`] fed by the records of the OneCRL collection (`security-state/onecrl`).
+///
+/// Certificates are revoked either by issuer and serial number, or by subject and
+/// public key hash.
+///
+/// # Examples
+/// ```no_run
+/// # use remote_settings_client::Client;
+/// # use remote_settings_client::client::OneCrl;
+/// # use std::sync::Arc;
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut onecrl_client = Client::builder()
+///   .bucket_name("security-state")
+///   .collection_name("onecrl")
+///   .signer_name("onecrl.content-signature.mozilla.org")
+///   .build()?;
+/// let onecrl = Arc::new(OneCrl::new(&onecrl_client.get().await?));
+///
+/// let client = Client::builder()
+///   .collection_name("cid")
+///   .revocation_checker(onecrl.clone())
+///   .build()?;
+///
+/// // Later, after synchronizing again.
+/// onecrl.update(&onecrl_client.sync(None).await?.records);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct OneCrl {
+    revocations: RwLock<Revocations>,
+}
+
+impl std::fmt::Debug for OneCrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OneCrl").finish_non_exhaustive()
+    }
+}
+
+impl OneCrl {
+    /// Creates a checker from the records of the OneCRL collection.
+    pub fn new(records: &[Record]) -> Self {
+        let onecrl = OneCrl::default();
+        onecrl.update(records);
+        onecrl
+    }
+
+    /// Replaces the revocations with the records of the OneCRL collection.
+    ///
+    /// Records that cannot be parsed are ignored.
+    pub fn update(&self, records: &[Record]) {
+        let mut revocations = Revocations::default();
+        for record in records {
+            let entry = match record.deserialize::<OneCrlEntry>() {
+                Ok(entry) => entry,
+                Err(err) => {
+                    warn!("Ignore OneCRL entry: {}", err);
+                    continue;
+                }
+            };
+            let decode =
+                |field: &Option<String>| field.as_ref().and_then(|v| STANDARD.decode(v).ok());
+            match (
+                decode(&entry.issuer_name),
+                decode(&entry.serial_number),
+                decode(&entry.subject),
+                decode(&entry.pub_key_hash),
+            ) {
+                (Some(issuer), Some(serial_number), _, _) => {
+                    revocations.by_issuer.insert((issuer, serial_number));
+                }
+                (_, _, Some(subject), Some(pub_key_hash)) => {
+                    revocations.by_subject.insert((subject, pub_key_hash));
+                }
+                _ => warn!("Ignore OneCRL entry {}: missing fields", record.id()),
+            }
+        }
+        *self.revocations.write().unwrap() = revocations;
+    }
+}
+
+impl RevocationChecker for OneCrl {
+    fn is_revoked(&self, certificate: &CertificateInfo) -> bool {
+        let revocations = self.revocations.read().unwrap();
+        let by_issuer = (
+            certificate.issuer.to_vec(),
+            certificate.serial_number.to_vec(),
+        );
+        let by_subject = (
+            certificate.subject.to_vec(),
+            Sha256::digest(certificate.subject_public_key_info).to_vec(),
+        );
+        revocations.by_issuer.contains(&by_issuer) || revocations.by_subject.contains(&by_subject)
+    }
+}
+
+/// A verifier that checks every certificate of the chain with a [`RevocationChecker`],
+/// before verifying the chain with another verifier.
+pub(crate) struct RevocationVerifier {
+    inner: Box<dyn Verification>,
+    checker: Arc<dyn RevocationChecker>,
+}
+
+impl RevocationVerifier {
+    pub fn new(inner: Box<dyn Verification>, checker: Arc<dyn RevocationChecker>) -> Self {
+        Self { inner, checker }
+    }
+
+    fn check_revocations(&self, pem_bytes: &[u8]) -> Result<(), SignatureError> {
+        for pem in Pem::iter_from_buffer(pem_bytes) {
+            let pem =
+                pem.map_err(|err| SignatureError::CertificateContentError(err.to_string()))?;
+            let cert = pem
+                .parse_x509()
+                .map_err(|err| SignatureError::CertificateContentError(err.to_string()))?;
+            let tbs = &cert.tbs_certificate;
+            let info = CertificateInfo {
+                issuer: tbs.issuer.as_raw(),
+                serial_number: tbs.raw_serial(),
+                subject: tbs.subject.as_raw(),
+                subject_public_key_info: tbs.subject_pki.raw,
+            };
+            if self.checker.is_revoked(&info) {
+                return Err(SignatureError::CertificateRevoked(tbs.subject.to_string()));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Verification for RevocationVerifier {
+    async fn fetch_certificate_chain(
+        &self,
+        requester: &'_ (dyn Requester + 'static),
+        collection: &Collection,
+    ) -> Result<Vec<u8>, SignatureError> {
+        self.inner
+            .fetch_certificate_chain(requester, collection)
+            .await
+    }
+
+    fn serialize_data(&self, collection: &Collection) -> Result<Vec<u8>, SignatureError> {
+        self.inner.serialize_data(collection)
+    }
+
+    fn verify_nist384p_chain(
+        &self,
+        epoch_seconds: u64,
+        pem_bytes: &[u8],
+        root_hash: &str,
+        subject_cn: &str,
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<(), SignatureError> {
+        self.check_revocations(pem_bytes)?;
+        self.inner.verify_nist384p_chain(
+            epoch_seconds,
+            pem_bytes,
+            root_hash,
+            subject_cn,
+            message,
+            signature,
+        )
+    }
+
+    fn verify_nist384p_chain_with_clock(
+        &self,
+        clock: &ServerClock,
+        pem_bytes: &[u8],
+        root_hash: &str,
+        subject_cn: &str,
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<(), SignatureError> {
+        self.check_revocations(pem_bytes)?;
+        self.inner.verify_nist384p_chain_with_clock(
+            clock, pem_bytes, root_hash, subject_cn, message, signature,
+        )
+    }
+
+    fn verify_sha256_hash(&self, content: &[u8], expected: &[u8]) -> Result<(), SignatureError> {
+        self.inner.verify_sha256_hash(content, expected)
+    }
+
+    fn verify_sha256_digest(&self, digest: &[u8], expected: &[u8]) -> Result<(), SignatureError> {
+        self.inner.verify_sha256_digest(digest, expected)
+    }
+
+    fn checks_signatures(&self) -> bool {
+        self.inner.checks_signatures()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OneCrl, RevocationVerifier};
+    use crate::client::signatures::certificate_cache::tests::CERTIFICATE;
+    use crate::client::{Record, SignatureError, Verification};
+    use serde_json::json;
+    use std::sync::Arc;
+
+    // DER of `CN=test`.
+    const NAME: &str = "MA8xDTALBgNVBAMMBHRlc3Q=";
+
+    struct AcceptingVerifier {}
+
+    impl Verification for AcceptingVerifier {
+        fn verify_nist384p_chain(
+            &self,
+            _: u64,
+            _: &[u8],
+            _: &str,
+            _: &str,
+            _: &[u8],
+            _: &[u8],
+        ) -> Result<(), SignatureError> {
+            Ok(())
+        }
+
+        fn verify_sha256_hash(&self, _: &[u8], _: &[u8]) -> Result<(), SignatureError> {
+            Ok(())
+        }
+    }
+
+    fn verify_with(records: Vec<Record>) -> Result<(), SignatureError> {
+        let verifier = RevocationVerifier::new(
+            Box::new(AcceptingVerifier {}),
+            Arc::new(OneCrl::new(&records)),
+        );
+        verifier.verify_nist384p_chain(0, CERTIFICATE.as_bytes(), "", "test", b"", b"")
+    }
+
+    #[test]
+    fn test_revoked_by_issuer_and_serial_number() {
+        let revoked = Record::new(json!({
+            "id": "a",
+            "last_modified": 1,
+            "issuerName": NAME,
+            "serialNumber": "Yi5z1RC/2EIIq5yJu3r/axLB0UY=",
+        }));
+        let other = Record::new(json!({
+            "id": "b",
+            "last_modified": 1,
+            "issuerName": NAME,
+            "serialNumber": "AQ==",
+        }));
+
+        assert!(verify_with(vec![other.clone()]).is_ok());
+        match verify_with(vec![other, revoked]) {
+            Err(SignatureError::CertificateRevoked(subject)) => assert_eq!(subject, "CN=test"),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_revoked_by_subject_and_public_key() {
+        let revoked = Record::new(json!({
+            "id": "a",
+            "last_modified": 1,
+            "subject": NAME,
+            "pubKeyHash": "lEvx2L1f2o+s4VgSxQzO/HiQ3BX92MkXJjpuIG9fr/c=",
+        }));
+        let invalid = Record::new(json!({
+            "id": "b",
+            "last_modified": 1,
+            "subject": "%^",
+        }));
+
+        assert!(verify_with(vec![invalid.clone()]).is_ok());
+        assert!(matches!(
+            verify_with(vec![invalid, revoked]),
+            Err(SignatureError::CertificateRevoked(_))
+        ));
+    }
+}
@@ -3,16 +3,37 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use super::{x509, SignatureError, Verification};
+use crate::client::net::ServerClock;
 use async_trait::async_trait;
 use base64::{engine::general_purpose::URL_SAFE, Engine as _};
 use hex;
 use ring::digest::{Context, SHA256};
 use ring::signature;
-use x509_parser::time::ASN1Time;
 
-pub struct RingVerifier {}
+pub struct RingVerifier {
+    cn_fallback: bool,
+}
+
+impl RingVerifier {
+    /// Creates a verifier that requires the signer name in the subject alternative name
+    /// of the end-entity certificate.
+    pub fn new() -> Self {
+        RingVerifier { cn_fallback: false }
+    }
 
-impl RingVerifier {}
+    /// Whether the common name of the end-entity certificate is compared to the signer
+    /// name when the certificate has no subject alternative name (*default*: `false`).
+    pub fn cn_fallback(mut self, enabled: bool) -> Self {
+        self.cn_fallback = enabled;
+        self
+    }
+}
+
+impl Default for RingVerifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 #[async_trait]
 impl Verification for RingVerifier {
@@ -24,6 +45,24 @@ impl Verification for RingVerifier {
         subject_cn: &str,
         message: &[u8],
         signature: &[u8],
+    ) -> Result<(), SignatureError> {
+        let clock = ServerClock {
+            now: epoch_seconds,
+            tolerance: 0,
+        };
+        self.verify_nist384p_chain_with_clock(
+            &clock, pem_bytes, root_hash, subject_cn, message, signature,
+        )
+    }
+
+    fn verify_nist384p_chain_with_clock(
+        &self,
+        clock: &ServerClock,
+        pem_bytes: &[u8],
+        root_hash: &str,
+        subject_cn: &str,
+        message: &[u8],
+        signature: &[u8],
     ) -> Result<(), SignatureError> {
         // 1. Parse the PEM bytes as DER-encoded X.509 Certificate.
         let pems = match x509::parse_certificate_chain(pem_bytes) {
@@ -57,17 +96,9 @@ impl Verification for RingVerifier {
             )));
         }
 
-        // 3. Verify that each certificate of the chain is currently valid (no revocation support)
-        // TODO: take clock skew into account
-        let now = match ASN1Time::from_timestamp(epoch_seconds as i64) {
-            Ok(t) => t,
-            Err(_) => return Err(SignatureError::CertificateExpired),
-        };
-        for cert in &certs {
-            if !cert.tbs_certificate.validity.is_valid_at(now) {
-                return Err(SignatureError::CertificateExpired);
-            }
-        }
+        // 3. Verify that each certificate of the chain is currently valid, within the tolerated
+        // clock skew (revocations are checked by the caller)
+        x509::verify_validity(&certs, clock)?;
 
         // 4. Verify that each child signature matches its parent's public key for each pair in the chain
         for pair in certs.windows(2) {
@@ -104,17 +135,8 @@ impl Verification for RingVerifier {
         let leaf_cert = certs.last().unwrap(); // PEM parse fails if len == 0.
 
         // 5. Verify that the subject alternate name of the end-entity certificate matches the collection signer name.
-        let leaf_subject = leaf_cert
-            .tbs_certificate
-            .subject
-            .iter_common_name()
-            .next()
-            .and_then(|cn| cn.as_str().ok())
-            .unwrap_or("")
-            .to_string();
-        if leaf_subject != subject_cn {
-            return Err(SignatureError::InvalidCertificateSubject(leaf_subject));
-        }
+        x509::verify_end_entity(leaf_cert, subject_cn, self.cn_fallback)?;
+
         // 6. Use the chain's end-entity (leaf) certificate to verify that the "signature" property matches the contents of the data.
         let public_key_bytes = &leaf_cert
             .tbs_certificate
@@ -146,3 +168,15 @@ impl Verification for RingVerifier {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::RingVerifier;
+    use crate::client::signatures::x509::tests::verify_end_entity_in_chain;
+
+    #[test]
+    fn test_end_entity() {
+        verify_end_entity_in_chain(&RingVerifier::new());
+        verify_end_entity_in_chain(&RingVerifier::new().cn_fallback(true));
+    }
+}
@@ -0,0 +1,200 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use super::{x509, SignatureError, Verification};
+use crate::client::net::ServerClock;
+use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE, Engine as _};
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::signature::Verifier;
+use rsa::traits::PublicKeyParts;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::convert::TryFrom;
+use x509_parser::oid_registry;
+
+/// Minimum size of the RSA keys of the chain, like `ring`.
+const RSA_MIN_BITS: usize = 2048;
+
+/// A signature verifier leveraging the pure-Rust [RustCrypto](https://github.com/RustCrypto) crates.
+pub struct RustCryptoVerifier {
+    cn_fallback: bool,
+}
+
+impl RustCryptoVerifier {
+    /// Creates a verifier that requires the signer name in the subject alternative name
+    /// of the end-entity certificate.
+    pub fn new() -> Self {
+        RustCryptoVerifier { cn_fallback: false }
+    }
+
+    /// Whether the common name of the end-entity certificate is compared to the signer
+    /// name when the certificate has no subject alternative name (*default*: `false`).
+    pub fn cn_fallback(mut self, enabled: bool) -> Self {
+        self.cn_fallback = enabled;
+        self
+    }
+}
+
+impl Default for RustCryptoVerifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn verify_rsa<D>(public_key: &[u8], message: &[u8], signature: &[u8]) -> Result<(), ()>
+where
+    D: Digest + rsa::pkcs8::AssociatedOid,
+{
+    let public_key = rsa::RsaPublicKey::from_pkcs1_der(public_key).map_err(|_| ())?;
+    if public_key.size() * 8 < RSA_MIN_BITS {
+        return Err(());
+    }
+    let signature = rsa::pkcs1v15::Signature::try_from(signature).map_err(|_| ())?;
+    rsa::pkcs1v15::VerifyingKey::<D>::new(public_key)
+        .verify(message, &signature)
+        .map_err(|_| ())
+}
+
+fn verify_p256(public_key: &[u8], message: &[u8], signature: &[u8]) -> Result<(), ()> {
+    let public_key = p256::ecdsa::VerifyingKey::from_sec1_bytes(public_key).map_err(|_| ())?;
+    let signature = p256::ecdsa::Signature::from_der(signature).map_err(|_| ())?;
+    public_key.verify(message, &signature).map_err(|_| ())
+}
+
+fn verify_p384(public_key: &[u8], message: &[u8], signature: &[u8]) -> Result<(), ()> {
+    let public_key = p384::ecdsa::VerifyingKey::from_sec1_bytes(public_key).map_err(|_| ())?;
+    let signature = p384::ecdsa::Signature::from_der(signature).map_err(|_| ())?;
+    public_key.verify(message, &signature).map_err(|_| ())
+}
+
+#[async_trait]
+impl Verification for RustCryptoVerifier {
+    fn verify_nist384p_chain(
+        &self,
+        epoch_seconds: u64,
+        pem_bytes: &[u8],
+        root_hash: &str,
+        subject_cn: &str,
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<(), SignatureError> {
+        let clock = ServerClock {
+            now: epoch_seconds,
+            tolerance: 0,
+        };
+        self.verify_nist384p_chain_with_clock(
+            &clock, pem_bytes, root_hash, subject_cn, message, signature,
+        )
+    }
+
+    fn verify_nist384p_chain_with_clock(
+        &self,
+        clock: &ServerClock,
+        pem_bytes: &[u8],
+        root_hash: &str,
+        subject_cn: &str,
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<(), SignatureError> {
+        // 1. Parse the PEM bytes as DER-encoded X.509 Certificate.
+        let pems = x509::parse_certificate_chain(pem_bytes)
+            .map_err(|err| SignatureError::CertificateContentError(err.to_string()))?;
+        let certs = pems
+            .iter()
+            .map(x509::parse_x509_certificate)
+            .collect::<Result<Vec<x509::X509Certificate>, _>>()
+            .map_err(|err| SignatureError::CertificateContentError(err.to_string()))?;
+
+        // 2. Verify that root hash matches the SHA256 fingerprint of the root certificate (DER content)
+        let root_hash_bytes = hex::decode(root_hash.replace(':', ""))
+            .map_err(|err| SignatureError::RootHashFormatError(err.to_string()))?;
+
+        let root_pem = pems.first().unwrap();
+
+        let root_fingerprint_bytes = Sha256::digest(&root_pem.contents).to_vec();
+        if root_fingerprint_bytes != root_hash_bytes {
+            return Err(SignatureError::InvalidCertificateIssuer(hex::encode(
+                root_fingerprint_bytes,
+            )));
+        }
+
+        // 3. Verify that each certificate of the chain is currently valid, within the tolerated
+        // clock skew (revocations are checked by the caller)
+        x509::verify_validity(&certs, clock)?;
+
+        // 4. Verify that each child signature matches its parent's public key for each pair in the chain
+        for pair in certs.windows(2) {
+            if let [parent, child] = pair {
+                let signature_alg = &child.signature_algorithm.algorithm;
+                let verify_child = if *signature_alg == oid_registry::OID_PKCS1_SHA256WITHRSA {
+                    verify_rsa::<Sha256>
+                } else if *signature_alg == oid_registry::OID_PKCS1_SHA384WITHRSA {
+                    verify_rsa::<Sha384>
+                } else if *signature_alg == oid_registry::OID_PKCS1_SHA512WITHRSA {
+                    verify_rsa::<Sha512>
+                } else if *signature_alg == oid_registry::OID_SIG_ECDSA_WITH_SHA256 {
+                    verify_p256
+                } else if *signature_alg == oid_registry::OID_SIG_ECDSA_WITH_SHA384 {
+                    verify_p384
+                } else {
+                    return Err(SignatureError::UnsupportedSignatureAlgorithm);
+                };
+
+                let parent_pk_bytes = &parent.tbs_certificate.subject_pki.subject_public_key.data;
+                let child_der_bytes = child.tbs_certificate.as_ref();
+                let child_sig_bytes = &child.signature_value.data;
+
+                verify_child(parent_pk_bytes, child_der_bytes, child_sig_bytes)
+                    .or(Err(SignatureError::CertificateTrustError))?;
+            }
+        }
+
+        let leaf_cert = certs.last().unwrap(); // PEM parse fails if len == 0.
+
+        // 5. Verify that the subject alternate name of the end-entity certificate matches the collection signer name.
+        x509::verify_end_entity(leaf_cert, subject_cn, self.cn_fallback)?;
+
+        // 6. Use the chain's end-entity (leaf) certificate to verify that the "signature" property matches the contents of the data.
+        let public_key_bytes = &leaf_cert
+            .tbs_certificate
+            .subject_pki
+            .subject_public_key
+            .data;
+        let public_key = p384::ecdsa::VerifyingKey::from_sec1_bytes(public_key_bytes)
+            .map_err(|err| SignatureError::MismatchError(err.to_string()))?;
+
+        let decoded_signature = URL_SAFE
+            .decode(signature)
+            .map_err(|err| SignatureError::BadSignatureContent(err.to_string()))?;
+        let signature = p384::ecdsa::Signature::from_slice(&decoded_signature)
+            .map_err(|err| SignatureError::MismatchError(err.to_string()))?;
+
+        public_key
+            .verify(message, &signature)
+            .map_err(|err| SignatureError::MismatchError(err.to_string()))
+    }
+
+    fn verify_sha256_hash(&self, content: &[u8], expected: &[u8]) -> Result<(), SignatureError> {
+        let actual = Sha256::digest(content);
+        if expected == &actual[..] {
+            Ok(())
+        } else {
+            Err(SignatureError::MismatchError(
+                "content did not match expected sha256 hash".to_string(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RustCryptoVerifier;
+    use crate::client::signatures::x509::tests::verify_end_entity_in_chain;
+
+    #[test]
+    fn test_end_entity() {
+        verify_end_entity_in_chain(&RustCryptoVerifier::new());
+        verify_end_entity_in_chain(&RustCryptoVerifier::new().cn_fallback(true));
+    }
+}
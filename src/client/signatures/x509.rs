@@ -2,8 +2,12 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use super::{check_validity, SignatureError};
+use crate::client::net::ServerClock;
 use thiserror::Error;
-use x509_parser::{self, error as x509_errors, nom::Err as NomErr, pem::Pem};
+use x509_parser::{
+    self, error as x509_errors, extensions::GeneralName, nom::Err as NomErr, pem::Pem,
+};
 
 pub use x509_parser::certificate::X509Certificate;
 
@@ -48,9 +52,85 @@ pub fn parse_x509_certificate(pem: &Pem) -> Result<X509Certificate, X509Error> {
     Ok(cert)
 }
 
+/// Verify that each certificate of the chain is valid at the server time, within the
+/// tolerated clock skew.
+pub fn verify_validity(
+    certs: &[X509Certificate],
+    clock: &ServerClock,
+) -> Result<(), SignatureError> {
+    let periods: Vec<(i64, i64)> = certs
+        .iter()
+        .map(|cert| {
+            let validity = &cert.tbs_certificate.validity;
+            (
+                validity.not_before.timestamp(),
+                validity.not_after.timestamp(),
+            )
+        })
+        .collect();
+    check_validity(&periods, clock)
+}
+
+/// Verify that the end-entity certificate is allowed to sign content for the specified signer.
+///
+/// The signer name must be one of the `dNSName` entries of the subject alternative name,
+/// compared case-insensitively.
+/// If the certificate has none and `cn_fallback` is `true`, the common name is compared instead.
+/// The extended key usage must contain code signing.
+pub fn verify_end_entity(
+    cert: &X509Certificate,
+    signer: &str,
+    cn_fallback: bool,
+) -> Result<(), SignatureError> {
+    let dns_names: Vec<&str> = match cert.subject_alternative_name() {
+        Ok(Some(san)) => san
+            .value
+            .general_names
+            .iter()
+            .filter_map(|name| match name {
+                GeneralName::DNSName(dns_name) => Some(*dns_name),
+                _ => None,
+            })
+            .collect(),
+        Ok(None) => vec![],
+        Err(err) => return Err(SignatureError::CertificateContentError(err.to_string())),
+    };
+    if dns_names.is_empty() && cn_fallback {
+        let common_name = cert
+            .subject()
+            .iter_common_name()
+            .next()
+            .and_then(|cn| cn.as_str().ok())
+            .unwrap_or("");
+        if !common_name.eq_ignore_ascii_case(signer) {
+            return Err(SignatureError::InvalidCertificateSubject(
+                common_name.to_string(),
+            ));
+        }
+    } else if !dns_names
+        .iter()
+        .any(|dns_name| dns_name.eq_ignore_ascii_case(signer))
+    {
+        return Err(SignatureError::InvalidCertificateSubject(
+            dns_names.join(", "),
+        ));
+    }
+
+    match cert.extended_key_usage() {
+        Ok(Some(eku)) if eku.value.code_signing => Ok(()),
+        Ok(_) => Err(SignatureError::InvalidCertificateUsage),
+        Err(err) => Err(SignatureError::CertificateContentError(err.to_string())),
+    }
+}
+
 #[cfg(test)]
-mod tests {
-    use super::parse_certificate_chain;
+pub(crate) mod tests {
+    use super::{
+        parse_certificate_chain, parse_x509_certificate, verify_end_entity, verify_validity,
+    };
+    use crate::client::net::ServerClock;
+    use crate::client::{SignatureError, Verification};
+    use sha2::{Digest, Sha256};
 
     #[test]
     fn test_bad_pem_content() {
@@ -86,4 +166,200 @@ bGxhIEFNTyBQcm9kdWN0aW9uIFNp
             assert_eq!(err.to_string(), error);
         }
     }
+
+    // Certificates for `signer.example.com`, with or without subject alternative name
+    // and extended key usage.
+    const SAN_CERTIFICATE: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBxDCCAWugAwIBAgIUYhQ/jIbmk86xpK5F8Fhz6dmtvkIwCgYIKoZIzj0EAwIw
+HDEaMBgGA1UEAwwRb3RoZXIuZXhhbXBsZS5jb20wIBcNMjYxMDE2MjA0OTMxWhgP
+MjEyNjA5MjIyMDQ5MzFaMBwxGjAYBgNVBAMMEW90aGVyLmV4YW1wbGUuY29tMFkw
+EwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEqa1wZ1msDO4/6fy5uYprjhzmRB7Jjzt+
+wJIfzNCMF+1uw/tUEqMz/+mqljXTVcfspxNllBTa9ORwiWSNfnalj6OBiDCBhTAd
+BgNVHQ4EFgQUNrjxDVbNTYwSBVohN+dZZmfPHpowHwYDVR0jBBgwFoAUNrjxDVbN
+TYwSBVohN+dZZmfPHpowDwYDVR0TAQH/BAUwAwEB/zAdBgNVHREEFjAUghJzaWdu
+ZXIuZXhhbXBsZS5jb20wEwYDVR0lBAwwCgYIKwYBBQUHAwMwCgYIKoZIzj0EAwID
+RwAwRAIgQBsvwQw4orMULmHGA0mxp6UrcTLZSTTj7pTqkt3XOGsCIBwG3mQqnj4a
+Ncp5E++h31qmpkVqhHQeld8ZKsTEitnY
+-----END CERTIFICATE-----";
+    const CN_CERTIFICATE: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBpzCCAUygAwIBAgIUM3wPnxS0IO4SwNlOevOlpAcpvZ8wCgYIKoZIzj0EAwIw
+HTEbMBkGA1UEAwwSc2lnbmVyLmV4YW1wbGUuY29tMCAXDTI2MTAxNjIwNDkzMVoY
+DzIxMjYwOTIyMjA0OTMxWjAdMRswGQYDVQQDDBJzaWduZXIuZXhhbXBsZS5jb20w
+WTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAASmkPiVmIINNX4wZnTycIZ1pwvhEyrf
+AVB5TNnLNfBXbcCYT/+0rHFlAuK+sW3QTPxjuucbQtLl8cNCB3i4Wo0no2gwZjAd
+BgNVHQ4EFgQUVMOtiV7vf0IJiiy+rfSXvXppw0YwHwYDVR0jBBgwFoAUVMOtiV7v
+f0IJiiy+rfSXvXppw0YwDwYDVR0TAQH/BAUwAwEB/zATBgNVHSUEDDAKBggrBgEF
+BQcDAzAKBggqhkjOPQQDAgNJADBGAiEA6DH8nKrzPpzxVdsQnTY78e5LstXb29KB
+sELYN2dN5dUCIQDFP3O8kjxHCaNmeBFWU/Y7We9R5VJ2CxyLUI0kg9hPsA==
+-----END CERTIFICATE-----";
+    const MIXED_CASE_CN_CERTIFICATE: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBpTCCAUygAwIBAgIUI0mqPiGFNY3WhYQXEOG2uuZxSgMwCgYIKoZIzj0EAwIw
+HTEbMBkGA1UEAwwSU2lnbmVyLkV4YW1wbGUuQ09NMCAXDTI2MTAxNjIyNTE0M1oY
+DzIxMjYwOTIyMjI1MTQzWjAdMRswGQYDVQQDDBJTaWduZXIuRXhhbXBsZS5DT00w
+WTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAARoV5IG2II5Q45ltjkbOFk3n1wFSt0p
+oLt99/nwuPYnXFXhiX/V+d+PQXLq6WQatZdX2nI3J09yYrUC89PUpY2Bo2gwZjAP
+BgNVHRMBAf8EBTADAQH/MBMGA1UdJQQMMAoGCCsGAQUFBwMDMB0GA1UdDgQWBBQO
+iESlIjypnFsH2tCawtrm0PDLdDAfBgNVHSMEGDAWgBQOiESlIjypnFsH2tCawtrm
+0PDLdDAKBggqhkjOPQQDAgNHADBEAiAygDUgOq4vwI9vKtEpiHVRtqyM1XcAPDkl
+EdAG5lShWgIgKW58mwkuSFvk05ka5cfs5OHMuBrAcw3hlojj7YfXEWY=
+-----END CERTIFICATE-----";
+    const NO_EKU_CERTIFICATE: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBrzCCAVagAwIBAgIUF3UQAz9VomsO2tFzC1w4x/jr+rAwCgYIKoZIzj0EAwIw
+HTEbMBkGA1UEAwwSc2lnbmVyLmV4YW1wbGUuY29tMCAXDTI2MTAxNjIwNDkzMVoY
+DzIxMjYwOTIyMjA0OTMxWjAdMRswGQYDVQQDDBJzaWduZXIuZXhhbXBsZS5jb20w
+WTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAATshaKt0+2BGYCTcgr+DpDIWwdIvr+/
+1ZLZ3WGUzKL4fK5uGA7MuTQ3h/iIo+zvPFhpNvOG0wVXO4N6K4Khr6yIo3IwcDAd
+BgNVHQ4EFgQU2iiQ3bjgh8umWcGPprjMdk1uNigwHwYDVR0jBBgwFoAU2iiQ3bjg
+h8umWcGPprjMdk1uNigwDwYDVR0TAQH/BAUwAwEB/zAdBgNVHREEFjAUghJzaWdu
+ZXIuZXhhbXBsZS5jb20wCgYIKoZIzj0EAwIDRwAwRAIgXZStYyBILvxm8K+knmW9
+Y5pH10qvtDLGdwFxa7eX22gCICA1O83reASvoqIF5xUNRL39wC7or+4F2Dz8Z+6R
+VYcQ
+-----END CERTIFICATE-----";
+    const SERVER_AUTH_CERTIFICATE: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBxjCCAW2gAwIBAgIUQ9wYoOtXlzhORcGZB130QFJ+0IgwCgYIKoZIzj0EAwIw
+HTEbMBkGA1UEAwwSc2lnbmVyLmV4YW1wbGUuY29tMCAXDTI2MTAxNjIwNDkzMVoY
+DzIxMjYwOTIyMjA0OTMxWjAdMRswGQYDVQQDDBJzaWduZXIuZXhhbXBsZS5jb20w
+WTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAATD5Rg6tSh+GTaUtgSKTSGi6p/1iu3t
+vXIHx9dH4aMgE4E5YP6kKrLEoSQStjnywLLEqZGs7VNiIijTdG0yKIB+o4GIMIGF
+MB0GA1UdDgQWBBQiyvdq6nTNze6hlAbwn2ZAj7cToDAfBgNVHSMEGDAWgBQiyvdq
+6nTNze6hlAbwn2ZAj7cToDAPBgNVHRMBAf8EBTADAQH/MB0GA1UdEQQWMBSCEnNp
+Z25lci5leGFtcGxlLmNvbTATBgNVHSUEDDAKBggrBgEFBQcDATAKBggqhkjOPQQD
+AgNHADBEAiAylIhhd4MqYsCClKPL9bEouDkXHDymnsyUPwAnydsq+gIgCx7lYBoZ
+St6HBwFVJd9TyGic6UhfeAsWSxQzmPGHDr0=
+-----END CERTIFICATE-----";
+
+    fn verify(pem: &str, signer: &str, cn_fallback: bool) -> Result<(), SignatureError> {
+        let pems = parse_certificate_chain(pem.as_bytes()).unwrap();
+        let cert = parse_x509_certificate(&pems[0]).unwrap();
+        verify_end_entity(&cert, signer, cn_fallback)
+    }
+
+    #[test]
+    fn test_subject_alternative_name() {
+        assert!(verify(SAN_CERTIFICATE, "signer.example.com", false).is_ok());
+        assert!(verify(SAN_CERTIFICATE, "Signer.Example.COM", false).is_ok());
+        // The common name is ignored when the certificate has alternative names.
+        match verify(SAN_CERTIFICATE, "other.example.com", true) {
+            Err(SignatureError::InvalidCertificateSubject(names)) => {
+                assert_eq!(names, "signer.example.com")
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_common_name_fallback() {
+        assert!(verify(CN_CERTIFICATE, "signer.example.com", true).is_ok());
+        // DNS names are case-insensitive.
+        assert!(verify(CN_CERTIFICATE, "Signer.Example.COM", true).is_ok());
+        assert!(verify(MIXED_CASE_CN_CERTIFICATE, "signer.example.com", true).is_ok());
+        assert!(matches!(
+            verify(CN_CERTIFICATE, "other.example.com", true),
+            Err(SignatureError::InvalidCertificateSubject(_))
+        ));
+        assert!(matches!(
+            verify(CN_CERTIFICATE, "signer.example.com", false),
+            Err(SignatureError::InvalidCertificateSubject(_))
+        ));
+    }
+
+    #[test]
+    fn test_code_signing_usage() {
+        for pem in &[NO_EKU_CERTIFICATE, SERVER_AUTH_CERTIFICATE] {
+            assert!(matches!(
+                verify(pem, "signer.example.com", false),
+                Err(SignatureError::InvalidCertificateUsage)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_validity_clock_skew() {
+        let pems = parse_certificate_chain(CN_CERTIFICATE.as_bytes()).unwrap();
+        let certs = vec![parse_x509_certificate(&pems[0]).unwrap()];
+        // The certificate is valid from 2026-10-16T20:49:31Z.
+        let not_before = 1792183771;
+        let clock = |now, tolerance| ServerClock { now, tolerance };
+
+        assert!(verify_validity(&certs, &clock(not_before, 0)).is_ok());
+        assert!(matches!(
+            verify_validity(&certs, &clock(not_before - 60, 0)),
+            Err(SignatureError::CertificateExpired)
+        ));
+        assert!(verify_validity(&certs, &clock(not_before - 60, 300)).is_ok());
+        assert!(matches!(
+            verify_validity(&certs, &clock(not_before - 600, 300)),
+            Err(SignatureError::CertificateExpired)
+        ));
+    }
+
+    // Self-signed P-384 certificates for `signer.example.com`, valid from 2026-10-16T22:37:36Z,
+    // with a subject alternative name only (the common name is `other.example.com`), or
+    // without extended key usage.
+    const P384_SAN_CERTIFICATE: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBzjCCAVSgAwIBAgIUBQVdiaZDQUdbGytO/iE7iOYnBWMwCgYIKoZIzj0EAwIw
+HDEaMBgGA1UEAwwRb3RoZXIuZXhhbXBsZS5jb20wIBcNMjYxMDE2MjIzNzM2WhgP
+MjEyNjA5MjIyMjM3MzZaMBwxGjAYBgNVBAMMEW90aGVyLmV4YW1wbGUuY29tMHYw
+EAYHKoZIzj0CAQYFK4EEACIDYgAEVrH9OB1kw5ZfWO8a1+6G6RyNYUG3TgfwOiOg
+vc/IoUuvEQ1FCMX+kuFfTNreuoDohnhOMPRliHD8rn08t7ipz8mJypVGGN70pODO
+vmkP5QO93vns4NokHODCmB6Ai91mo1UwUzAdBgNVHREEFjAUghJzaWduZXIuZXhh
+bXBsZS5jb20wEwYDVR0lBAwwCgYIKwYBBQUHAwMwHQYDVR0OBBYEFG7S+a7M0hXn
+4Xdut0POEv6o48fQMAoGCCqGSM49BAMCA2gAMGUCMHeFPfTU08Lrc65aukgoWGtJ
+h+2QxOtGIE4Z9csKfRDrXLAxKOIiaBXmAJWcYOZnxwIxAIJl8gVuHQOKx/Fo3wrL
+fNUa7o+7CVnCzvEeVfwGaPhN/5mGgbNvLYqzrUZK/u5k7Q==
+-----END CERTIFICATE-----";
+    const P384_NO_EKU_CERTIFICATE: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBuzCCAUGgAwIBAgIUIhkpRn9XYmE0SkyEMgbwAgk5wGowCgYIKoZIzj0EAwIw
+HTEbMBkGA1UEAwwSc2lnbmVyLmV4YW1wbGUuY29tMCAXDTI2MTAxNjIyMzczNloY
+DzIxMjYwOTIyMjIzNzM2WjAdMRswGQYDVQQDDBJzaWduZXIuZXhhbXBsZS5jb20w
+djAQBgcqhkjOPQIBBgUrgQQAIgNiAARWsf04HWTDll9Y7xrX7obpHI1hQbdOB/A6
+I6C9z8ihS68RDUUIxf6S4V9M2t66gOiGeE4w9GWIcPyufTy3uKnPyYnKlUYY3vSk
+4M6+aQ/lA73e+ezg2iQc4MKYHoCL3WajQDA+MB0GA1UdEQQWMBSCEnNpZ25lci5l
+eGFtcGxlLmNvbTAdBgNVHQ4EFgQUbtL5rszSFefhd263Q84S/qjjx9AwCgYIKoZI
+zj0EAwIDaAAwZQIwNMErBRvpe/0mL/DG9rJTiZdBRotjdLTMBhFMtd1nvezVBYm7
+g0dFH3QNqFXMBannAjEAhhGpgbkk35fHXHEX5dKK9P4boN4q/RJDCO38fLBqWadK
+pzPn2udESMHTHbIcY0Q3
+-----END CERTIFICATE-----";
+    // Signature of `P384_MESSAGE` by the key of both certificates.
+    const P384_MESSAGE: &[u8] = b"Content-Signature:\x00{\"data\":[],\"last_modified\":\"42\"}";
+    const P384_SIGNATURE: &str = "3G58BIgYDWpyNClDk-tJl15UdiShYdRNIHatIrHHerJSatGcjklYMxxHU5PI1pfiw6iWsktENrWyjJe85W8Z6bX8Jv9OLfkpmqwOnuLYfcb2YioZTBALWvm5Ydv7Jr1I";
+
+    /// Check that a verifier matches the signer name with the subject alternative name,
+    /// and requires the code signing usage, when verifying a chain.
+    pub fn verify_end_entity_in_chain(verifier: &dyn Verification) {
+        let clock = ServerClock {
+            now: 1792200000,
+            tolerance: 0,
+        };
+        let verify = |pem: &str, signer: &str| {
+            let root_hash = hex::encode(Sha256::digest(
+                &parse_certificate_chain(pem.as_bytes()).unwrap()[0].contents,
+            ));
+            verifier.verify_nist384p_chain_with_clock(
+                &clock,
+                pem.as_bytes(),
+                &root_hash,
+                signer,
+                P384_MESSAGE,
+                P384_SIGNATURE.as_bytes(),
+            )
+        };
+
+        assert!(verify(P384_SAN_CERTIFICATE, "signer.example.com").is_ok());
+        assert!(matches!(
+            verify(P384_SAN_CERTIFICATE, "other.example.com"),
+            Err(SignatureError::InvalidCertificateSubject(_))
+        ));
+        assert!(matches!(
+            verify(P384_NO_EKU_CERTIFICATE, "signer.example.com"),
+            Err(SignatureError::InvalidCertificateUsage)
+        ));
+    }
 }
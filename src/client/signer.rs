@@ -0,0 +1,192 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use super::{Environment, DEFAULT_SIGNER_NAME};
+use serde_json::Value;
+
+/// Domain of the signer names, appended to signer IDs (eg. `onecrl`).
+const SIGNER_NAME_SUFFIX: &str = "content-signature.mozilla.org";
+
+/// A hook to choose the expected signer name of a collection.
+///
+/// It is consulted before the server capabilities and the bucket defaults.
+/// It is implemented for closures.
+///
+/// # Examples
+/// ```rust
+/// # use remote_settings_client::Client;
+/// # use serde_json::Value;
+/// # use std::sync::Arc;
+/// let client = Client::builder()
+///   .collection_name("cid")
+///   .signer_resolver(Arc::new(|bid: &str, _cid: &str, _metadata: &Value| {
+///     if bid == "security-state" {
+///       Some("onecrl.content-signature.mozilla.org".to_string())
+///     } else {
+///       None
+///     }
+///   }))
+///   .build()
+///   .unwrap();
+/// ```
+pub trait SignerResolver: Send + Sync {
+    /// Return the signer name of the collection, or `None` to resolve it automatically.
+    fn signer_name(&self, bid: &str, cid: &str, metadata: &Value) -> Option<String>;
+}
+
+impl<F> SignerResolver for F
+where
+    F: Fn(&str, &str, &Value) -> Option<String> + Send + Sync,
+{
+    fn signer_name(&self, bid: &str, cid: &str, metadata: &Value) -> Option<String> {
+        self(bid, cid, metadata)
+    }
+}
+
+impl std::fmt::Debug for dyn SignerResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "dyn SignerResolver")
+    }
+}
+
+/// Return the signer name for a signer ID, like `onecrl.content-signature.mozilla.org`
+/// for `onecrl`.
+fn signer_name_from_id(signer_id: &str) -> String {
+    if signer_id.contains('.') {
+        signer_id.to_string()
+    } else {
+        format!("{}.{}", signer_id, SIGNER_NAME_SUFFIX)
+    }
+}
+
+/// Return the signer name from the signature of the collection metadata, if specified.
+///
+/// The metadata is not signed: it is only compared with the expected signer.
+pub(crate) fn signer_from_metadata(metadata: &Value) -> Option<String> {
+    metadata["signature"]["signer_id"]
+        .as_str()
+        .map(signer_name_from_id)
+}
+
+/// Return the signer name from the resources of the `signer` server capability.
+///
+/// A resource of the collection takes precedence over a resource of its bucket.
+pub(crate) fn signer_from_capabilities(
+    server_info: &Value,
+    bid: &str,
+    cid: &str,
+) -> Option<String> {
+    let resources = server_info["capabilities"]["signer"]["resources"].as_array()?;
+    let mut bucket_signer = None;
+    for resource in resources {
+        let destination = &resource["destination"];
+        let signer_id = match resource["signer_id"].as_str() {
+            Some(signer_id) if destination["bucket"] == bid => signer_id,
+            _ => continue,
+        };
+        match destination["collection"].as_str() {
+            Some(collection) if collection == cid => return Some(signer_name_from_id(signer_id)),
+            None => bucket_signer = Some(signer_name_from_id(signer_id)),
+            Some(_) => (),
+        }
+    }
+    bucket_signer
+}
+
+/// Return the signer name of the collection in the environment, when nothing else is known.
+pub(crate) fn default_signer_name(environment: &Environment, bid: &str, cid: &str) -> String {
+    if !environment.uses_mozilla_signers() {
+        return DEFAULT_SIGNER_NAME.to_string();
+    }
+    let signer_id = match (bid, cid) {
+        ("security-state", _) | ("security-state-preview", _) => "onecrl",
+        ("blocklists", "certificates") => "onecrl",
+        _ => "remote-settings",
+    };
+    signer_name_from_id(signer_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{default_signer_name, signer_from_capabilities, signer_from_metadata};
+    use crate::client::Environment;
+    use serde_json::json;
+
+    #[test]
+    fn test_signer_from_metadata() {
+        let metadata = json!({"signature": {"signer_id": "onecrl"}});
+        assert_eq!(
+            signer_from_metadata(&metadata).unwrap(),
+            "onecrl.content-signature.mozilla.org"
+        );
+        let metadata = json!({"signature": {"signer_id": "signer.example.com"}});
+        assert_eq!(
+            signer_from_metadata(&metadata).unwrap(),
+            "signer.example.com"
+        );
+        assert!(signer_from_metadata(&json!({"signature": {}})).is_none());
+    }
+
+    #[test]
+    fn test_signer_from_capabilities() {
+        let server_info = json!({
+            "capabilities": {
+                "signer": {
+                    "resources": [
+                        {
+                            "destination": {"bucket": "main", "collection": null},
+                            "signer_id": "remote-settings"
+                        },
+                        {
+                            "destination": {"bucket": "main", "collection": "cid"},
+                            "signer_id": "normandy"
+                        },
+                        {
+                            "destination": {"bucket": "security-state", "collection": null}
+                        }
+                    ]
+                }
+            }
+        });
+
+        let signer = |bid, cid| signer_from_capabilities(&server_info, bid, cid);
+        assert_eq!(
+            signer("main", "cid").unwrap(),
+            "normandy.content-signature.mozilla.org"
+        );
+        assert_eq!(
+            signer("main", "other").unwrap(),
+            "remote-settings.content-signature.mozilla.org"
+        );
+        assert!(signer("security-state", "onecrl").is_none());
+        assert!(signer_from_capabilities(&json!({}), "main", "cid").is_none());
+    }
+
+    #[test]
+    fn test_default_signer_name() {
+        for environment in &[Environment::Prod, Environment::Stage] {
+            assert_eq!(
+                default_signer_name(environment, "security-state", "onecrl"),
+                "onecrl.content-signature.mozilla.org"
+            );
+            assert_eq!(
+                default_signer_name(environment, "blocklists", "certificates"),
+                "onecrl.content-signature.mozilla.org"
+            );
+            assert_eq!(
+                default_signer_name(environment, "blocklists", "plugins"),
+                "remote-settings.content-signature.mozilla.org"
+            );
+        }
+
+        let custom = Environment::Custom {
+            server_url: "https://example.com/v1".to_string(),
+            cert_root_hash: "00".to_string(),
+        };
+        assert_eq!(
+            default_signer_name(&custom, "security-state", "onecrl"),
+            "remote-settings.content-signature.mozilla.org"
+        );
+    }
+}
@@ -5,12 +5,21 @@
 pub mod dummy_storage;
 pub mod file_storage;
 pub mod memory_storage;
+#[cfg(feature = "sqlite_storage")]
+pub mod sqlite_storage;
 
+use serde_json::{Map, Value};
 use thiserror::Error;
 
 /// A trait for giving a type a custom storage implementation
 ///
 /// The `Storage` is used to store the collection content locally.
+///
+/// Only [`Storage::store`] and [`Storage::retrieve`] have to be implemented. The
+/// records methods store all records of a collection under a single key by default.
+/// [`Storage::update_records`] and [`Storage::retrieve_records`] should be overridden
+/// when the storage can update records individually.
+///
 /// # How can I implement ```Storage```?
 /// ```rust
 /// # use remote_settings_client::{SignatureError, Verification, Storage, StorageError};
@@ -41,6 +50,113 @@ pub trait Storage: Send + Sync {
     ///
     /// If an error occurs while reading, a [`StorageError::ReadError`] is returned.
     fn retrieve(&self, key: &str) -> Result<Vec<u8>, StorageError>;
+
+    /// Delete a key. Unknown keys are ignored.
+    ///
+    /// By default, the value is replaced by an empty one, which the client treats as missing.
+    ///
+    /// # Errors
+    /// If an error occurs while deleting, a [`StorageError::WriteError`] is returned.
+    fn delete(&mut self, key: &str) -> Result<(), StorageError> {
+        match self.retrieve(key) {
+            Ok(_) => self.store(key, Vec::new()),
+            Err(StorageError::KeyNotFound { .. }) => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Insert or replace records of a collection, identified by their `id` field, and
+    /// delete records by id, at once. Deletions are applied first. Unknown ids are ignored.
+    ///
+    /// # Errors
+    /// If a record has no `id` field, or if an error occurs while storing, a
+    /// [`StorageError::WriteError`] is returned.
+    fn update_records(
+        &mut self,
+        collection: &str,
+        upserted: Vec<Value>,
+        deleted: &[&str],
+    ) -> Result<(), StorageError> {
+        let ids = upserted
+            .iter()
+            .map(|record| record_id(record).map(str::to_string))
+            .collect::<Result<Vec<String>, StorageError>>()?;
+        let mut stored = retrieve_records_map(self, collection)?;
+        for id in deleted {
+            stored.remove(*id);
+        }
+        for (id, record) in ids.into_iter().zip(upserted) {
+            stored.insert(id, record);
+        }
+        store_records_map(self, collection, stored)
+    }
+
+    /// Insert or replace records of a collection, identified by their `id` field.
+    ///
+    /// # Errors
+    /// If a record has no `id` field, or if an error occurs while storing, a
+    /// [`StorageError::WriteError`] is returned.
+    fn upsert_records(
+        &mut self,
+        collection: &str,
+        records: Vec<Value>,
+    ) -> Result<(), StorageError> {
+        self.update_records(collection, records, &[])
+    }
+
+    /// Delete records of a collection by id. Unknown ids are ignored.
+    ///
+    /// # Errors
+    /// If an error occurs while storing, a [`StorageError::WriteError`] is returned.
+    fn delete_records(&mut self, collection: &str, ids: &[&str]) -> Result<(), StorageError> {
+        self.update_records(collection, Vec::new(), ids)
+    }
+
+    /// Retrieve all records of a collection, in no particular order.
+    ///
+    /// An empty list is returned if no record was stored.
+    ///
+    /// # Errors
+    /// If an error occurs while reading, a [`StorageError::ReadError`] is returned.
+    fn retrieve_records(&self, collection: &str) -> Result<Vec<Value>, StorageError> {
+        Ok(retrieve_records_map(self, collection)?
+            .into_iter()
+            .map(|(_, v)| v)
+            .collect())
+    }
+}
+
+/// Return the `id` field of a record to store.
+pub(crate) fn record_id(record: &Value) -> Result<&str, StorageError> {
+    record["id"]
+        .as_str()
+        .ok_or_else(|| StorageError::WriteError("cannot store a record without id".to_string()))
+}
+
+fn records_key(collection: &str) -> String {
+    format!("{}:records", collection)
+}
+
+fn retrieve_records_map<S: Storage + ?Sized>(
+    storage: &S,
+    collection: &str,
+) -> Result<Map<String, Value>, StorageError> {
+    match storage.retrieve(&records_key(collection)) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .map_err(|err| StorageError::ReadError(format!("cannot deserialize records: {}", err))),
+        Err(StorageError::KeyNotFound { .. }) => Ok(Map::new()),
+        Err(err) => Err(err),
+    }
+}
+
+fn store_records_map<S: Storage + ?Sized>(
+    storage: &mut S,
+    collection: &str,
+    records: Map<String, Value>,
+) -> Result<(), StorageError> {
+    let bytes = serde_json::to_vec(&records)
+        .map_err(|err| StorageError::WriteError(format!("cannot serialize records: {}", err)))?;
+    storage.store(&records_key(collection), bytes)
 }
 
 #[derive(Debug, PartialEq, Error)]
@@ -52,3 +168,79 @@ pub enum StorageError {
     #[error("key could not be found: {key}")]
     KeyNotFound { key: String },
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{memory_storage::MemoryStorage, Storage, StorageError};
+    use serde_json::json;
+
+    /// A storage that only implements the required methods.
+    struct KeyValueStorage(MemoryStorage);
+
+    impl Storage for KeyValueStorage {
+        fn store(&mut self, key: &str, value: Vec<u8>) -> Result<(), StorageError> {
+            self.0.store(key, value)
+        }
+
+        fn retrieve(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+            self.0.retrieve(key)
+        }
+    }
+
+    /// Exercise the records methods of the specified storage.
+    pub fn check_records_storage(storage: &mut dyn Storage) {
+        assert!(storage.retrieve_records("main/cid").unwrap().is_empty());
+
+        storage
+            .upsert_records(
+                "main/cid",
+                vec![json!({"id": "a", "v": 1}), json!({"id": "b", "v": 1})],
+            )
+            .unwrap();
+        storage
+            .upsert_records("main/other", vec![json!({"id": "a", "v": 0})])
+            .unwrap();
+        storage
+            .upsert_records("main/cid", vec![json!({"id": "b", "v": 2})])
+            .unwrap();
+        storage
+            .delete_records("main/cid", &["a", "unknown"])
+            .unwrap();
+        storage
+            .update_records(
+                "main/other",
+                vec![json!({"id": "c", "v": 0}), json!({"id": "a", "v": 1})],
+                &["a", "c"],
+            )
+            .unwrap();
+
+        assert_eq!(
+            storage.retrieve_records("main/cid").unwrap(),
+            vec![json!({"id": "b", "v": 2})]
+        );
+        let mut other = storage.retrieve_records("main/other").unwrap();
+        other.sort_by_key(|record| record["id"].to_string());
+        assert_eq!(
+            other,
+            vec![json!({"id": "a", "v": 1}), json!({"id": "c", "v": 0})]
+        );
+
+        // Records without id are rejected, and nothing is written.
+        assert!(matches!(
+            storage.upsert_records(
+                "main/cid",
+                vec![json!({"id": "d", "v": 0}), json!({"v": 0})]
+            ),
+            Err(StorageError::WriteError(_))
+        ));
+        assert_eq!(
+            storage.retrieve_records("main/cid").unwrap(),
+            vec![json!({"id": "b", "v": 2})]
+        );
+    }
+
+    #[test]
+    fn test_default_records_methods() {
+        check_records_storage(&mut KeyValueStorage(MemoryStorage::new()));
+    }
+}
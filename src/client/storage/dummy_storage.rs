@@ -16,4 +16,8 @@ impl Storage for DummyStorage {
             key: key.to_string(),
         })
     }
+
+    fn delete(&mut self, _key: &str) -> Result<(), StorageError> {
+        Ok(())
+    }
 }
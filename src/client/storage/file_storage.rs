@@ -3,13 +3,30 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use {
-    super::{Storage, StorageError},
-    log::{debug, error},
-    std::fs::OpenOptions,
+    super::{record_id, Storage, StorageError},
+    log::{debug, error, warn},
+    serde_json::Value,
+    std::collections::hash_map::RandomState,
+    std::fs::{self, OpenOptions},
+    std::hash::{BuildHasher, Hasher},
     std::io::prelude::*,
     std::path::{Path, PathBuf},
 };
 
+/// Suffix of the temporary files, written before being renamed over the previous values.
+const TMP_SUFFIX: &str = ".tmp";
+/// Extension of the folders that contain the records of a collection.
+const RECORDS_EXTENSION: &str = "records";
+
+/// A storage that writes each key into a separate file of the specified folder.
+///
+/// The records of each collection are written into separate files of a sub-folder, so
+/// that synchronizations only write the records that changed.
+///
+/// Writes are atomic: values are written into a temporary file, which is then
+/// renamed over the previous one. If the process is interrupted in the middle of
+/// a write, the previous value is kept, and the partially written file is ignored.
+/// Such files are removed by [`FileStorage::open`].
 pub struct FileStorage {
     pub folder: PathBuf,
     pub extension: String,
@@ -31,6 +48,43 @@ impl From<std::io::Error> for StorageError {
 }
 
 impl FileStorage {
+    /// Use the specified folder, and remove the temporary files left behind by
+    /// interrupted writes.
+    ///
+    /// No other instance should be writing into the folder at the same time.
+    pub fn open<P: Into<PathBuf>>(folder: P) -> Self {
+        let storage = FileStorage {
+            folder: folder.into(),
+            ..FileStorage::default()
+        };
+        storage.remove_tmp_files(&storage.folder);
+        if let Ok(entries) = fs::read_dir(&storage.folder) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.is_dir() && path.extension() == Some(RECORDS_EXTENSION.as_ref()) {
+                    storage.remove_tmp_files(&path);
+                }
+            }
+        }
+        storage
+    }
+
+    fn remove_tmp_files(&self, folder: &Path) {
+        let entries = match fs::read_dir(folder) {
+            Ok(entries) => entries,
+            // The folder is created on the first write.
+            Err(_) => return,
+        };
+        let pattern = format!(".{}.", self.extension);
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.ends_with(TMP_SUFFIX) && name.contains(&pattern) {
+                warn!("Remove partially written file {:?}", entry.path());
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    }
+
     fn _pathfor(&self, key: &str) -> PathBuf {
         let slug = key
             .chars()
@@ -49,32 +103,111 @@ impl FileStorage {
 
         p
     }
-}
 
-impl Storage for FileStorage {
-    fn store(&mut self, key: &str, value: Vec<u8>) -> Result<(), StorageError> {
-        let path = self._pathfor(key);
+    /// Return the folder of the records of a collection, which contains one file per record.
+    fn _recordsfolderfor(&self, collection: &str) -> PathBuf {
+        let mut p = self._pathfor(collection);
+        p.set_extension(RECORDS_EXTENSION);
+        p
+    }
+
+    fn _recordpathfor(&self, collection: &str, id: &str) -> PathBuf {
+        // Unlike keys, distinct ids must not share the same file.
+        let slug = id
+            .bytes()
+            .map(|b| match b {
+                b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' => (b as char).to_string(),
+                _ => format!("+{:02x}", b),
+            })
+            .collect::<String>();
+
+        let mut p = self._recordsfolderfor(collection).join(slug);
+        p.set_extension(&self.extension);
+
+        p
+    }
+
+    /// Return a unique temporary path for a write of the file, so that concurrent
+    /// writers of the same key don't write into the same file.
+    fn _tmppathfor(path: &Path) -> PathBuf {
+        unique_path(path, TMP_SUFFIX)
+    }
+
+    /// Write the file atomically, without persisting its rename in the folder.
+    fn write_file(path: &Path, value: &[u8]) -> Result<(), StorageError> {
+        let tmp_path = Self::_tmppathfor(path);
+
         match OpenOptions::new()
             .write(true)
-            .truncate(true)
-            .create(true)
-            .open(&path)
+            .create_new(true)
+            .open(&tmp_path)
         {
             Err(err) => {
-                error!("Couldn't open or create {:?}: {}", path, err);
+                error!("Couldn't open or create {:?}: {}", tmp_path, err);
                 Err(StorageError::WriteError(err.to_string()))
             }
             Ok(mut file) => {
-                file.write_all(&value)?;
-                file.sync_all()?;
-                debug!("Wrote {} ({} bytes) into {:?}", key, value.len(), path);
+                let written = file
+                    .write_all(value)
+                    .and_then(|_| file.sync_all())
+                    // Replace the previous file only once the new content is on disk.
+                    .and_then(|_| fs::rename(&tmp_path, path));
+                if let Err(err) = written {
+                    error!("Couldn't write {:?}: {}", path, err);
+                    let _ = fs::remove_file(&tmp_path);
+                    return Err(err.into());
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Persist the renames and removals of files in the folder.
+    fn sync_folder(folder: &Path) -> Result<(), StorageError> {
+        // Folders cannot be opened on Windows.
+        if cfg!(unix) {
+            fs::File::open(folder)?.sync_all()?;
+        }
+        Ok(())
+    }
+
+    fn create_folder(folder: &Path) -> Result<(), StorageError> {
+        fs::create_dir_all(folder).map_err(|err| {
+            error!("Couldn't create folder {:?}: {}", folder, err);
+            err.into()
+        })
+    }
+}
+
+impl Storage for FileStorage {
+    fn store(&mut self, key: &str, value: Vec<u8>) -> Result<(), StorageError> {
+        let path = self._pathfor(key);
+
+        Self::create_folder(&self.folder)?;
+        Self::write_file(&path, &value)?;
+        Self::sync_folder(&self.folder)?;
+        debug!("Wrote {} ({} bytes) into {:?}", key, value.len(), path);
+        Ok(())
+    }
+
+    fn delete(&mut self, key: &str) -> Result<(), StorageError> {
+        let path = self._pathfor(key);
+        match fs::remove_file(&path) {
+            Ok(()) => {
+                debug!("Deleted {} from {:?}", key, path);
                 Ok(())
             }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => {
+                error!("Couldn't delete {:?}: {}", path, err);
+                Err(err.into())
+            }
         }
     }
 
     fn retrieve(&self, key: &str) -> Result<Vec<u8>, StorageError> {
         let path = self._pathfor(key);
+
         let mut file = match OpenOptions::new().read(true).write(false).open(&path) {
             Ok(file) => file,
             Err(err) => {
@@ -96,14 +229,105 @@ impl Storage for FileStorage {
 
         Ok(s.into_bytes())
     }
+
+    fn update_records(
+        &mut self,
+        collection: &str,
+        upserted: Vec<Value>,
+        deleted: &[&str],
+    ) -> Result<(), StorageError> {
+        let folder = self._recordsfolderfor(collection);
+        let files = upserted
+            .iter()
+            .map(|record| {
+                let bytes = serde_json::to_vec(record).map_err(|err| {
+                    StorageError::WriteError(format!("cannot serialize record: {}", err))
+                })?;
+                Ok((self._recordpathfor(collection, record_id(record)?), bytes))
+            })
+            .collect::<Result<Vec<_>, StorageError>>()?;
+
+        for id in deleted {
+            let path = self._recordpathfor(collection, id);
+            match fs::remove_file(&path) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                    error!("Couldn't delete {:?}: {}", path, err);
+                    return Err(err.into());
+                }
+                _ => {}
+            }
+        }
+        if !files.is_empty() {
+            Self::create_folder(&folder)?;
+        }
+        for (path, bytes) in &files {
+            Self::write_file(path, bytes)?;
+        }
+        if folder.exists() {
+            Self::sync_folder(&folder)?;
+        }
+        debug!(
+            "Wrote {} and deleted {} records of {} in {:?}",
+            files.len(),
+            deleted.len(),
+            collection,
+            folder
+        );
+        Ok(())
+    }
+
+    fn retrieve_records(&self, collection: &str) -> Result<Vec<Value>, StorageError> {
+        let folder = self._recordsfolderfor(collection);
+        let entries = match fs::read_dir(&folder) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(StorageError::ReadError(err.to_string())),
+        };
+        let mut records = Vec::new();
+        for entry in entries {
+            let path = entry
+                .map_err(|err| StorageError::ReadError(err.to_string()))?
+                .path();
+            // Temporary files are ignored.
+            if path.extension() != Some(self.extension.as_ref()) {
+                continue;
+            }
+            let bytes = fs::read(&path).map_err(|err| {
+                error!("Couldn't read {:?}: {}", path, err);
+                StorageError::ReadError(err.to_string())
+            })?;
+            records.push(serde_json::from_slice(&bytes).map_err(|err| {
+                StorageError::ReadError(format!("cannot deserialize record: {}", err))
+            })?);
+        }
+        debug!(
+            "Read {} records of {} from {:?}",
+            records.len(),
+            collection,
+            folder
+        );
+        Ok(records)
+    }
+}
+
+/// Return a path next to the specified one, unique to this process and this call,
+/// ending with the specified suffix.
+pub(crate) fn unique_path(path: &Path, suffix: &str) -> PathBuf {
+    let random = RandomState::new().build_hasher().finish();
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}-{:016x}{}", std::process::id(), random, suffix));
+    path.with_file_name(name)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{FileStorage, Storage};
+    use super::{FileStorage, Storage, StorageError};
+    use crate::client::storage::tests::check_records_storage;
     use env_logger;
     use log::error;
-    use std::fs::remove_file;
+    use serde_json::json;
+    use std::fs::{read_dir, remove_dir_all, remove_file, write};
+    use std::thread;
 
     fn init() {
         let _ = env_logger::builder().is_test(true).try_init();
@@ -154,6 +378,22 @@ mod tests {
         cleanup("./overwrite-key.bin");
     }
 
+    #[test]
+    fn test_delete() {
+        init();
+
+        let mut storage = FileStorage::default();
+
+        storage
+            .store("delete-key", "some value".as_bytes().to_vec())
+            .unwrap();
+        storage.delete("delete-key").unwrap();
+        assert!(storage.retrieve("delete-key").is_err());
+
+        // Deleting an unknown key is not an error.
+        storage.delete("delete-key").unwrap();
+    }
+
     #[test]
     fn test_retrieve_cannot_find_file() {
         init();
@@ -184,4 +424,133 @@ mod tests {
 
         remove_file("./a_bid+a-cid+Records.bin").unwrap(); // Fails if file is missing.
     }
+
+    #[test]
+    fn test_store_creates_folder() {
+        init();
+
+        let folder = std::env::temp_dir().join("test_store_creates_folder");
+        let _ = remove_dir_all(&folder);
+        let mut storage = FileStorage {
+            folder: folder.join("sub"),
+            ..FileStorage::default()
+        };
+
+        storage
+            .store("some-key", "some value".as_bytes().to_vec())
+            .unwrap();
+
+        assert_eq!(storage.retrieve("some-key").unwrap(), b"some value");
+        assert_eq!(read_dir(folder.join("sub")).unwrap().count(), 1);
+        remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn test_partially_written_file_is_ignored() {
+        init();
+
+        let folder = std::env::temp_dir().join("test_partially_written_file_is_ignored");
+        let _ = remove_dir_all(&folder);
+        let mut storage = FileStorage::open(&folder);
+
+        // Interrupted first write.
+        storage.store("other-key", vec![]).unwrap();
+        write(folder.join("some-key.bin.1-0123.tmp"), "{\"records\": [").unwrap();
+
+        assert_eq!(
+            storage.retrieve("some-key").unwrap_err(),
+            StorageError::KeyNotFound {
+                key: "some-key".to_string()
+            }
+        );
+
+        // Interrupted overwrite.
+        storage
+            .store("some-key", "some value".as_bytes().to_vec())
+            .unwrap();
+        write(folder.join("some-key.bin.1-4567.tmp"), "new va").unwrap();
+
+        assert_eq!(storage.retrieve("some-key").unwrap(), b"some value");
+
+        // Leftovers are removed when the storage is opened again.
+        let storage = FileStorage::open(&folder);
+        assert!(!folder.join("some-key.bin.1-0123.tmp").exists());
+        assert!(!folder.join("some-key.bin.1-4567.tmp").exists());
+        assert_eq!(storage.retrieve("some-key").unwrap(), b"some value");
+        remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_writes() {
+        init();
+
+        let folder = std::env::temp_dir().join("test_concurrent_writes");
+        let _ = remove_dir_all(&folder);
+        FileStorage::open(&folder)
+            .store("key", vec![b'0'; 10_000])
+            .unwrap();
+
+        let threads: Vec<_> = (b'1'..=b'4')
+            .map(|byte| {
+                let folder = folder.clone();
+                thread::spawn(move || {
+                    let mut storage = FileStorage {
+                        folder,
+                        ..FileStorage::default()
+                    };
+                    for _ in 0..20 {
+                        storage.store("key", vec![byte; 10_000]).unwrap();
+                        let value = storage.retrieve("key").unwrap();
+                        // Never a mix of several writes.
+                        assert!(value.len() == 10_000 && value.iter().all(|b| *b == value[0]));
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(read_dir(&folder).unwrap().count(), 1);
+        remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn test_records_methods() {
+        init();
+
+        let folder = std::env::temp_dir().join("test_file_records_methods");
+        let _ = remove_dir_all(&folder);
+        check_records_storage(&mut FileStorage::open(&folder));
+        remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn test_records_are_stored_in_separate_files() {
+        init();
+
+        let folder = std::env::temp_dir().join("test_records_are_stored_in_separate_files");
+        let _ = remove_dir_all(&folder);
+        let mut storage = FileStorage::open(&folder);
+
+        storage
+            .upsert_records(
+                "main/cid",
+                vec![
+                    json!({"id": "a.b", "v": 1}),
+                    json!({"id": "a+b", "v": 2}),
+                    json!({"id": "a/b", "v": 3}),
+                ],
+            )
+            .unwrap();
+
+        let mut files: Vec<String> = read_dir(folder.join("main+cid.records"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        assert_eq!(files, vec!["a+2bb.bin", "a+2eb.bin", "a+2fb.bin"]);
+        assert_eq!(storage.retrieve_records("main/cid").unwrap().len(), 3);
+        remove_dir_all(&folder).unwrap();
+    }
 }
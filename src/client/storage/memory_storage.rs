@@ -2,18 +2,21 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use super::{Storage, StorageError};
+use super::{record_id, Storage, StorageError};
+use serde_json::Value;
 use std::collections::HashMap;
 
 #[derive(Default)]
 pub struct MemoryStorage {
     mem: HashMap<String, Vec<u8>>,
+    records: HashMap<String, HashMap<String, Value>>,
 }
 
 impl MemoryStorage {
     pub fn new() -> Self {
         MemoryStorage {
             mem: HashMap::new(),
+            records: HashMap::new(),
         }
     }
 }
@@ -32,4 +35,48 @@ impl Storage for MemoryStorage {
             }),
         }
     }
+
+    fn delete(&mut self, key: &str) -> Result<(), StorageError> {
+        self.mem.remove(key);
+        Ok(())
+    }
+
+    fn update_records(
+        &mut self,
+        collection: &str,
+        upserted: Vec<Value>,
+        deleted: &[&str],
+    ) -> Result<(), StorageError> {
+        let ids = upserted
+            .iter()
+            .map(|record| record_id(record).map(str::to_string))
+            .collect::<Result<Vec<String>, StorageError>>()?;
+        let stored = self.records.entry(collection.to_string()).or_default();
+        for id in deleted {
+            stored.remove(*id);
+        }
+        for (id, record) in ids.into_iter().zip(upserted) {
+            stored.insert(id, record);
+        }
+        Ok(())
+    }
+
+    fn retrieve_records(&self, collection: &str) -> Result<Vec<Value>, StorageError> {
+        Ok(self
+            .records
+            .get(collection)
+            .map(|stored| stored.values().cloned().collect())
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MemoryStorage;
+    use crate::client::storage::tests::check_records_storage;
+
+    #[test]
+    fn test_records_methods() {
+        check_records_storage(&mut MemoryStorage::new());
+    }
 }
@@ -0,0 +1,249 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use {
+    super::{record_id, Storage, StorageError},
+    log::debug,
+    rusqlite::{params, Connection, OptionalExtension},
+    serde_json::Value,
+    std::path::Path,
+    std::sync::Mutex,
+};
+
+/// A storage backed by a single SQLite database file.
+///
+/// Each write is atomic, and several clients can share the same database
+/// (eg. one per collection). Records are stored in their own table, so that
+/// synchronizations only write the records that changed.
+///
+/// ```rust
+/// # #[cfg(feature = "sqlite_storage")] {
+/// # use remote_settings_client::Client;
+/// use remote_settings_client::client::SqliteStorage;
+///
+/// let path = std::env::temp_dir().join("remote-settings.sqlite");
+/// let client = Client::builder()
+///   .collection_name("cid")
+///   .storage(Box::new(SqliteStorage::open(path).unwrap()))
+///   .build()
+///   .unwrap();
+/// # }
+/// ```
+pub struct SqliteStorage {
+    // `Connection` is not `Sync`.
+    conn: Mutex<Connection>,
+}
+
+fn read_error(err: rusqlite::Error) -> StorageError {
+    StorageError::ReadError(err.to_string())
+}
+
+fn write_error(err: rusqlite::Error) -> StorageError {
+    StorageError::WriteError(err.to_string())
+}
+
+impl SqliteStorage {
+    /// Open the database at the specified path, creating it if necessary.
+    ///
+    /// # Errors
+    /// If the database cannot be opened, a [`StorageError::ReadError`] is returned.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, StorageError> {
+        let conn = Connection::open(path.as_ref()).map_err(read_error)?;
+        // Let readers and the writer of other connections work concurrently.
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .map_err(read_error)?;
+        debug!("Opened SQLite storage {:?}", path.as_ref());
+        Self::init(conn)
+    }
+
+    /// Open a database in memory.
+    pub fn open_in_memory() -> Result<Self, StorageError> {
+        Self::init(Connection::open_in_memory().map_err(read_error)?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, StorageError> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS storage (
+                key TEXT PRIMARY KEY,
+                value BLOB NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS records (
+                collection TEXT NOT NULL,
+                id TEXT NOT NULL,
+                value BLOB NOT NULL,
+                PRIMARY KEY (collection, id)
+            );",
+        )
+        .map_err(read_error)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> Result<std::sync::MutexGuard<'_, Connection>, StorageError> {
+        self.conn
+            .lock()
+            .map_err(|err| StorageError::ReadError(err.to_string()))
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn store(&mut self, key: &str, value: Vec<u8>) -> Result<(), StorageError> {
+        let size = value.len();
+        self.conn()?
+            .execute(
+                "INSERT OR REPLACE INTO storage (key, value) VALUES (?1, ?2)",
+                params![key, value],
+            )
+            .map_err(write_error)?;
+        debug!("Wrote {} ({} bytes)", key, size);
+        Ok(())
+    }
+
+    fn retrieve(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        self.conn()?
+            .query_row(
+                "SELECT value FROM storage WHERE key = ?1",
+                params![key],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(read_error)?
+            .ok_or_else(|| StorageError::KeyNotFound {
+                key: key.to_string(),
+            })
+    }
+
+    fn delete(&mut self, key: &str) -> Result<(), StorageError> {
+        self.conn()?
+            .execute("DELETE FROM storage WHERE key = ?1", params![key])
+            .map_err(write_error)?;
+        Ok(())
+    }
+
+    fn update_records(
+        &mut self,
+        collection: &str,
+        upserted: Vec<Value>,
+        deleted: &[&str],
+    ) -> Result<(), StorageError> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction().map_err(write_error)?;
+        {
+            let mut stmt = tx
+                .prepare("DELETE FROM records WHERE collection = ?1 AND id = ?2")
+                .map_err(write_error)?;
+            for id in deleted {
+                stmt.execute(params![collection, id]).map_err(write_error)?;
+            }
+            let mut stmt = tx
+                .prepare(
+                    "INSERT OR REPLACE INTO records (collection, id, value) VALUES (?1, ?2, ?3)",
+                )
+                .map_err(write_error)?;
+            for record in &upserted {
+                let value = serde_json::to_vec(record).map_err(|err| {
+                    StorageError::WriteError(format!("cannot serialize record: {}", err))
+                })?;
+                stmt.execute(params![collection, record_id(record)?, value])
+                    .map_err(write_error)?;
+            }
+        }
+        // Nothing is written if a record is rejected.
+        tx.commit().map_err(write_error)?;
+        debug!(
+            "Wrote {} and deleted {} records of {}",
+            upserted.len(),
+            deleted.len(),
+            collection
+        );
+        Ok(())
+    }
+
+    fn retrieve_records(&self, collection: &str) -> Result<Vec<Value>, StorageError> {
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare("SELECT value FROM records WHERE collection = ?1")
+            .map_err(read_error)?;
+        let rows = stmt
+            .query_map(params![collection], |row| row.get::<_, Vec<u8>>(0))
+            .map_err(read_error)?;
+        rows.map(|bytes| {
+            serde_json::from_slice(&bytes.map_err(read_error)?).map_err(|err| {
+                StorageError::ReadError(format!("cannot deserialize record: {}", err))
+            })
+        })
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SqliteStorage, Storage, StorageError};
+    use crate::client::storage::tests::check_records_storage;
+    use std::fs::remove_file;
+
+    #[test]
+    fn test_store_and_retrieve() {
+        let mut storage = SqliteStorage::open_in_memory().unwrap();
+
+        storage
+            .store("a_bid/a-cid:collection", b"some value".to_vec())
+            .unwrap();
+        storage
+            .store("a_bid/a-cid:collection", b"new value".to_vec())
+            .unwrap();
+
+        let value = storage.retrieve("a_bid/a-cid:collection").unwrap();
+        assert_eq!(value, b"new value");
+
+        storage.delete("a_bid/a-cid:collection").unwrap();
+        assert!(storage.retrieve("a_bid/a-cid:collection").is_err());
+    }
+
+    #[test]
+    fn test_retrieve_unknown_key() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+
+        assert_eq!(
+            storage.retrieve("unknown-key").unwrap_err(),
+            StorageError::KeyNotFound {
+                key: "unknown-key".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_records_methods() {
+        check_records_storage(&mut SqliteStorage::open_in_memory().unwrap());
+    }
+
+    #[test]
+    fn test_values_are_persisted() {
+        let path = std::env::temp_dir().join("test_values_are_persisted.sqlite");
+        let _ = remove_file(&path);
+
+        let mut storage = SqliteStorage::open(&path).unwrap();
+        storage.store("key", b"value".to_vec()).unwrap();
+        drop(storage);
+
+        let storage = SqliteStorage::open(&path).unwrap();
+        assert_eq!(storage.retrieve("key").unwrap(), b"value");
+
+        remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_open_invalid_database() {
+        let path = std::env::temp_dir().join("test_open_invalid_database.sqlite");
+        std::fs::write(&path, vec![42; 4096]).unwrap();
+
+        assert!(matches!(
+            SqliteStorage::open(&path),
+            Err(StorageError::ReadError(_))
+        ));
+
+        remove_file(&path).unwrap();
+    }
+}
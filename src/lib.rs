@@ -21,7 +21,7 @@
 //!     .bucket_name("main-preview")
 //!     .http_client(Box::new(ViaductClient))
 //!     .collection_name("search-config")
-//!     .verifier(Box::new(RingVerifier {}))
+//!     .verifier(Box::new(RingVerifier::new()))
 //!     .build()
 //!     .unwrap();
 //!
@@ -44,16 +44,22 @@ pub mod client;
 
 pub use client::Client;
 pub use client::Collection;
+pub use client::Environment;
 pub use client::Record;
+pub use client::RemoteSettings;
 pub use client::SignatureError;
 pub use client::Storage;
 pub use client::StorageError;
 pub use client::Verification;
+pub use client::VerificationMode;
 pub use client::DEFAULT_BUCKET_NAME;
 pub use client::DEFAULT_SERVER_URL;
 
 #[cfg(feature = "ring_verifier")]
 pub use crate::client::RingVerifier;
 
+#[cfg(feature = "rustcrypto_verifier")]
+pub use crate::client::RustCryptoVerifier;
+
 #[cfg(feature = "rc_crypto_verifier")]
 pub use crate::client::RcCryptoVerifier;